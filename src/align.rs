@@ -0,0 +1,185 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{FixedOffset, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone};
+use exif::{Exif, In, Rational, Tag, Value};
+use std::path::{Path, PathBuf};
+
+use photo_sorter::{
+    config::{ClockOffset, Config},
+    formats::read_exif,
+    metadata::{read_photo, Extras, Photo},
+    source::{TimeReader, TimeSource},
+    timestamp::CaptureTime,
+};
+
+use crate::cli::AlignArgs;
+
+/// Computes the clock offset of the camera of a photo and saves it to the config.
+pub fn align(args: &AlignArgs) -> Result<()> {
+    let path = config_path(args);
+    let mut config = Config::load(&path)?;
+    let reader = TimeReader::new(&[TimeSource::ExifOriginal, TimeSource::Container], &[]);
+
+    let photo = read_existing(&args.photo, &reader)?;
+    let time = photo
+        .timestamp
+        .map(|timestamp| timestamp.time)
+        .ok_or_else(|| {
+            anyhow!(
+                "Capture time of {} is not found.",
+                args.photo.to_string_lossy()
+            )
+        })?;
+    let Some(model) = photo.camera.name() else {
+        bail!(
+            "Camera of {} is not found in Exif.",
+            args.photo.to_string_lossy()
+        );
+    };
+
+    let delta = match &args.reference {
+        Some(reference) => {
+            let mut reference = read_existing(reference, &reader)?;
+            if reference.camera.name() == photo.camera.name()
+                && reference.camera.serial == photo.camera.serial
+            {
+                bail!("Both photos are taken by {model}.");
+            }
+            // The reference may have its own offset.
+            config.correct(&mut reference);
+            let reference_time = reference.timestamp.map(|timestamp| timestamp.time);
+            reference_time
+                .map(|reference_time| reference_time.instant() - time.instant())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Capture time of {} is not found.",
+                        reference.path.to_string_lossy()
+                    )
+                })?
+        }
+        None => {
+            let utc = gps_time(&read_exif(&args.photo)).ok_or_else(|| {
+                anyhow!("GPS time of {} is not found.", args.photo.to_string_lossy())
+            })?;
+            gps_offset(utc, &time, args.zone)
+                .ok_or_else(|| anyhow!("Time zone of {} is unknown.", time.local))?
+        }
+    };
+
+    let offset = ClockOffset(TimeDelta::seconds(
+        (delta.num_milliseconds() as f64 / 1000.0).round() as i64,
+    ));
+    match &photo.camera.serial {
+        Some(serial) => println!("{model} (serial {serial}): {offset}"),
+        None => println!("{model}: {offset}"),
+    }
+    if args.test {
+        return Ok(());
+    }
+
+    config.set_clock_offset(model, photo.camera.serial.clone(), offset);
+    config.save(&path)?;
+    println!("Saved to {}", path.to_string_lossy());
+    Ok(())
+}
+
+fn config_path(args: &AlignArgs) -> PathBuf {
+    if let Some(config) = &args.config {
+        return config.clone();
+    }
+    match &args.dir {
+        Some(dir) => Config::path(dir),
+        None => Config::find(args.photo.parent().unwrap_or_else(|| Path::new(""))),
+    }
+}
+
+fn read_existing(path: &Path, reader: &TimeReader) -> Result<Photo> {
+    if !path.is_file() {
+        bail!("Path {} is not found.", path.to_string_lossy());
+    }
+    let extras = Extras {
+        live: false,
+        camera: true,
+        shot: false,
+    };
+    Ok(read_photo(path, reader, extras))
+}
+
+/// Reads `GPSDateStamp` and `GPSTimeStamp`, which are in UTC.
+fn gps_time(exif: &[Exif]) -> Option<NaiveDateTime> {
+    let field = |tag| {
+        exif.iter()
+            .find_map(|exif| exif.get_field(tag, In::PRIMARY))
+    };
+    let Value::Ascii(date) = &field(Tag::GPSDateStamp)?.value else {
+        return None;
+    };
+    let date = String::from_utf8_lossy(date.first()?);
+    let date = NaiveDate::parse_from_str(date.trim_end_matches('\0').trim(), "%Y:%m:%d").ok()?;
+    let Value::Rational(hms) = &field(Tag::GPSTimeStamp)?.value else {
+        return None;
+    };
+    date.and_hms_opt(0, 0, 0)?
+        .checked_add_signed(time_of_day(hms)?)
+}
+
+/// Converts hours, minutes and seconds of `GPSTimeStamp`. Broken rationals are rejected.
+fn time_of_day(hms: &[Rational]) -> Option<TimeDelta> {
+    let [hours, minutes, seconds] = hms else {
+        return None;
+    };
+    let seconds = hours.to_f64() * 3600.0 + minutes.to_f64() * 60.0 + seconds.to_f64();
+    if !seconds.is_finite() {
+        return None;
+    }
+    TimeDelta::try_milliseconds((seconds * 1000.0).round() as i64)
+}
+
+/// Offset that turns the capture time into the local time of the GPS time.
+///
+/// The time zone of the camera is `zone`, `OffsetTimeOriginal` or the local time zone, in this order.
+fn gps_offset(
+    utc: NaiveDateTime,
+    time: &CaptureTime,
+    zone: Option<FixedOffset>,
+) -> Option<TimeDelta> {
+    let zone = zone
+        .or(time.offset)
+        .or_else(|| Local.offset_from_local_datetime(&time.local).single())?;
+    Some(utc + TimeDelta::seconds(zone.local_minus_utc() as i64) - time.local)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gps_offset() {
+        let utc =
+            NaiveDateTime::parse_from_str("2024-05-01 03:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let time = CaptureTime::from_exif("2024:05:01 11:56:48", None, Some("+09:00")).unwrap();
+        assert_eq!(gps_offset(utc, &time, None), Some(TimeDelta::seconds(192)));
+
+        // The zone given by the user wins.
+        let zone = "+08:00".parse().ok();
+        assert_eq!(
+            gps_offset(utc, &time, zone),
+            Some(TimeDelta::seconds(192 - 3600))
+        );
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        let rational = |num, denom| Rational { num, denom };
+        let hms = [rational(3, 1), rational(0, 1), rational(1250, 100)];
+        assert_eq!(time_of_day(&hms), Some(TimeDelta::milliseconds(10_812_500)));
+        assert_eq!(
+            time_of_day(&[rational(3, 1), rational(0, 1), rational(1, 0)]),
+            None
+        );
+        assert_eq!(
+            time_of_day(&[rational(0, 0), rational(0, 1), rational(0, 1)]),
+            None
+        );
+        assert_eq!(time_of_day(&hms[..2]), None);
+    }
+}
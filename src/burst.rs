@@ -0,0 +1,162 @@
+use chrono::TimeDelta;
+use exif::{Exif, In, Tag, Value};
+
+use crate::{group::Item, live::apple_maker_note_ascii, metadata::Photo};
+
+/// Max gap between frames of a burst. Frames within one second without sub-seconds have the same time.
+const BURST_GAP: TimeDelta = TimeDelta::seconds(1);
+/// Max gap between frames of an exposure bracket, which may have long exposures.
+const BRACKET_GAP: TimeDelta = TimeDelta::seconds(5);
+/// `ExposureMode` of auto bracket.
+const AUTO_BRACKET: u32 = 2;
+/// Tag of `BurstUUID` in the MakerNote of Apple devices.
+const APPLE_BURST_UUID: u16 = 0x000b;
+
+/// Prefix of the folders that bursts are moved into, e.g. `burst-042` or `burst-3-042`.
+const FOLDER_PREFIX: &str = "burst-";
+
+/// How a photo was shot, to find bursts and exposure brackets.
+#[derive(Clone, Default)]
+pub struct Shot {
+    /// `BurstUUID` in the MakerNote of Apple devices.
+    pub burst_id: Option<String>,
+    /// Whether `ExposureMode` is auto bracket.
+    pub auto_bracket: bool,
+    /// `ExposureBiasValue` in EV.
+    pub exposure_bias: Option<f64>,
+}
+
+impl Shot {
+    pub fn from_exif(exif: &[Exif]) -> Self {
+        let field = |tag| {
+            exif.iter()
+                .find_map(|exif| exif.get_field(tag, In::PRIMARY))
+        };
+        Self {
+            burst_id: field(Tag::MakerNote).and_then(|field| match &field.value {
+                Value::Undefined(note, _) => apple_maker_note_ascii(note, APPLE_BURST_UUID),
+                _ => None,
+            }),
+            auto_bracket: field(Tag::ExposureMode)
+                .and_then(|field| field.value.get_uint(0))
+                .is_some_and(|mode| mode == AUTO_BRACKET),
+            exposure_bias: field(Tag::ExposureBiasValue).and_then(|field| match &field.value {
+                Value::SRational(values) => values.first().map(|value| value.to_f64()),
+                _ => None,
+            }),
+        }
+    }
+}
+
+/// Splits sorted items into bursts and exposure brackets. Other items are alone.
+///
+/// Consecutive frames are in one burst if they share Apple `BurstUUID`, or are taken by
+/// one camera less than a second apart, or within a few seconds in auto bracket or
+/// with different exposure bias.
+pub fn split_bursts<'a, I: IntoIterator<Item = &'a Item>>(items: I) -> Vec<Vec<&'a Item>> {
+    let mut bursts: Vec<Vec<&Item>> = Vec::new();
+    for item in items {
+        match bursts.last_mut() {
+            Some(burst) if in_burst(&burst[burst.len() - 1].photos[0], &item.photos[0]) => {
+                burst.push(item)
+            }
+            _ => bursts.push(vec![item]),
+        }
+    }
+    bursts
+}
+
+/// Name of the folder of a burst. The event is in the name if photos are split into events.
+pub fn burst_folder(event: Option<usize>, seq: usize, seq_width: usize) -> String {
+    match event {
+        Some(event) => format!("{FOLDER_PREFIX}{event}-{seq:0seq_width$}"),
+        None => format!("{FOLDER_PREFIX}{seq:0seq_width$}"),
+    }
+}
+
+/// Checks whether the name is made by `burst_folder`.
+pub fn is_burst_folder(name: &str) -> bool {
+    name.strip_prefix(FOLDER_PREFIX).is_some_and(|rest| {
+        !rest.is_empty()
+            && rest
+                .split('-')
+                .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+fn in_burst(last: &Photo, photo: &Photo) -> bool {
+    if let (Some(id1), Some(id2)) = (&last.shot.burst_id, &photo.shot.burst_id) {
+        return id1 == id2;
+    }
+    let (Some(t1), Some(t2)) = (&last.timestamp, &photo.timestamp) else {
+        return false;
+    };
+    if last.camera.name() != photo.camera.name() || last.camera.serial != photo.camera.serial {
+        return false;
+    }
+
+    let gap = (t2.time.instant() - t1.time.instant()).abs();
+    let bracket = (last.shot.auto_bracket && photo.shot.auto_bracket)
+        || matches!(
+            (last.shot.exposure_bias, photo.shot.exposure_bias),
+            (Some(bias1), Some(bias2)) if bias1 != bias2
+        );
+    gap < BURST_GAP || (bracket && gap <= BRACKET_GAP)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::timestamp::{CaptureTime, Timestamp};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_split_bursts() {
+        let shots = [
+            ("2024:05:01 10:00:00", Some("100"), None),
+            ("2024:05:01 10:00:00", Some("400"), None),
+            ("2024:05:01 10:00:01", Some("200"), None),
+            // Bracket of long exposures.
+            ("2024:05:01 10:05:00", None, Some(-1.0)),
+            ("2024:05:01 10:05:03", None, Some(0.0)),
+            ("2024:05:01 10:05:06", None, Some(1.0)),
+            ("2024:05:01 10:09:00", None, None),
+        ];
+        let items: Vec<Item> = shots
+            .iter()
+            .map(|(time, subsec, bias)| Item {
+                photos: vec![Photo {
+                    path: PathBuf::from("a.jpg"),
+                    timestamp: Some(Timestamp {
+                        time: CaptureTime::from_exif(time, *subsec, None).unwrap(),
+                        source: "exif-original",
+                        camera_clock: true,
+                        correction: None,
+                    }),
+                    shot: Shot {
+                        exposure_bias: *bias,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }],
+                sidecars: Vec::new(),
+            })
+            .collect();
+        let lens: Vec<usize> = split_bursts(&items)
+            .iter()
+            .map(|burst| burst.len())
+            .collect();
+        assert_eq!(lens, vec![3, 3, 1]);
+    }
+
+    #[test]
+    fn test_burst_folder() {
+        assert_eq!(burst_folder(None, 7, 3), "burst-007");
+        assert_eq!(burst_folder(Some(2), 7, 1), "burst-2-7");
+        assert!(is_burst_folder("burst-007"));
+        assert!(is_burst_folder("burst-2-7"));
+        assert!(!is_burst_folder("burst-"));
+        assert!(!is_burst_folder("burst-2-"));
+        assert!(!is_burst_folder("burst-a"));
+    }
+}
@@ -0,0 +1,75 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// SHA-256 of the file in lower case hex.
+pub fn sha256(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex(hasher))
+}
+
+/// Copies the file with its modified time, and reads the copy back to check
+/// that it has the same SHA-256 as the source.
+pub fn copy_verified(from: &Path, to: &Path) -> io::Result<()> {
+    let mut source = fs::File::open(from)?;
+    let mut target = fs::File::create_new(to)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let len = source.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+        target.write_all(&buf[..len])?;
+    }
+
+    let metadata = source.metadata()?;
+    target.set_permissions(metadata.permissions())?;
+    target.set_modified(metadata.modified()?)?;
+    target.sync_all()?;
+    drop(target);
+
+    if sha256(to)? != hex(hasher) {
+        return Err(io::Error::other("checksum of the copy does not match"));
+    }
+    Ok(())
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_copy_verified() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        fs::write(&from, b"photo").unwrap();
+
+        copy_verified(&from, &to).unwrap();
+        assert_eq!(fs::read(&to).unwrap(), b"photo");
+        assert_eq!(
+            fs::metadata(&to).unwrap().modified().unwrap(),
+            fs::metadata(&from).unwrap().modified().unwrap()
+        );
+        assert_eq!(
+            sha256(&to).unwrap(),
+            "55c64d0fcd6f9d5f7c828093857e3fdfda68478bb4e9bd24d481ef391c7804e8"
+        );
+        // Existing files are never overwritten.
+        assert!(copy_verified(&from, &to).is_err());
+    }
+}
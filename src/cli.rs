@@ -1,7 +1,26 @@
-use std::{path::{Path, PathBuf}, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+};
 
 use anyhow::bail;
-use clap::Parser;
+use chrono::FixedOffset;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+
+use photo_sorter::{
+    config::Config,
+    duplicate::DuplicateMode,
+    event::EventGap,
+    filename::FilenamePattern,
+    formats::{extensions, ExtChange},
+    similar::HashAlgorithm,
+    source::{TimeReader, TimeSource},
+    template::{Layout, Template},
+    ScanOptions, Similarity, SortOptions, Transfer,
+};
+
+use crate::print::print_progress;
 
 #[derive(Clone)]
 pub struct DirPath(PathBuf);
@@ -9,22 +28,257 @@ pub struct DirPath(PathBuf);
 #[derive(Clone)]
 pub struct Delim(String);
 
+/// Unit of sorting in recursive mode
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Scope {
+    /// Sorts each directory on its own
+    Dir,
+    /// Sorts whole tree as one timeline
+    Tree,
+}
+
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+    #[clap(flatten)]
+    pub sort: SortArgs,
+    #[clap(short, long, default_value = "false")]
+    /// Test mode that only shows order, like `plan` without `-o`
+    pub test: bool,
+}
+
+// Options of sorting, shared by the default command and `plan`.
+// Not a doc comment, which would become the description of the command.
+#[derive(clap::Args)]
+#[clap(group(ArgGroup::new("destination").args(["layout", "output"]).multiple(true)))]
+pub struct SortArgs {
     /// Path to directory includes photos
-    pub dir: DirPath,
+    #[clap(required = true)]
+    dir: Option<DirPath>,
     /// Prefix delimiter
     #[clap(short, long, default_value = "__")]
     pub delim: Delim,
-    #[clap(short, long, default_value = "false")]
-    /// Test mode that only shows order
-    pub test: bool,
+    /// Template of new names (e.g. `{seq:04}_{date:%Y%m%d-%H%M%S}_{model}_{stem}.{ext}`, default: `{seq}<delim>{name}`)
+    #[clap(long)]
+    pub template: Option<Template>,
+    /// Sources of capture time, tried in order until one is found
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "exif-original,container,filename"
+    )]
+    pub time_source: Vec<TimeSource>,
+    /// Extra pattern of date and time in file names (e.g. `DSC_%Y%m%d_%H%M%S`), tried before built-in ones
+    #[clap(long)]
+    pub filename_pattern: Vec<FilenamePattern>,
+    /// Number of threads to read metadata (default: number of CPUs)
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
     /// Sorts latest to oldest order
     #[clap(long, default_value = "false")]
     pub desc: bool,
     /// Revert renamed files
     #[clap(short, long, default_value = "false")]
     pub revert: bool,
+    /// Adds (`png`, `+png`) or removes (`-mov`) extensions of files to be sorted
+    #[clap(long, value_delimiter = ',', allow_hyphen_values = true)]
+    pub ext: Vec<ExtChange>,
+    /// Detects files to be sorted by magic bytes instead of extensions
+    #[clap(long, default_value = "false")]
+    pub magic: bool,
+    /// Numbers files sharing a name stem (e.g. RAW+JPEG) separately
+    #[clap(long, default_value = "false")]
+    pub no_group: bool,
+    /// Finds files with the same content, and reports, skips, moves or hard links them
+    #[clap(long, value_enum, conflicts_with = "revert")]
+    pub duplicates: Option<DuplicateMode>,
+    /// Finds similar photos, e.g. resized or recompressed copies, by a perceptual hash
+    #[clap(long, value_enum, conflicts_with = "revert")]
+    pub similar: Option<HashAlgorithm>,
+    /// Max number of different bits of perceptual hashes for photos to be similar
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u32).range(0..=64), requires = "similar")]
+    pub similar_distance: u32,
+    /// Numbers only the highest resolution photo of similar ones, leaving the others as is
+    #[clap(long, default_value = "false", requires = "similar")]
+    pub keep_best: bool,
+    /// Numbers bursts and exposure brackets as one photo with letters (e.g. `042a`, `042b`)
+    #[clap(long, default_value = "false")]
+    pub bursts: bool,
+    /// Moves each burst into its own folder (e.g. `burst-042`)
+    #[clap(long, default_value = "false", requires = "bursts")]
+    pub burst_folders: bool,
+    /// Splits files into events where capture times are further apart than this (e.g. `3h`, `1h30m`)
+    #[clap(long)]
+    pub event_gap: Option<EventGap>,
+    /// Moves files into folders by a template (e.g. `YYYY/MM/DD`, `YYYY/YYYY-MM-DD`, `{date:%Y}/{model}`)
+    #[clap(long, conflicts_with = "revert")]
+    pub layout: Option<Layout>,
+    /// Copies sorted files into the directory, leaving the sources as is (root of the layout folders)
+    #[clap(long, conflicts_with = "revert")]
+    pub output: Option<PathBuf>,
+    /// Copies files into the layout folders instead of moving
+    #[clap(long, default_value = "false", requires = "destination")]
+    pub copy: bool,
+    /// Makes hard links instead of copies (source and destination need to be on the same file system)
+    #[clap(long, default_value = "false", requires = "destination")]
+    pub hardlink: bool,
+    /// Config file with camera clock offsets (default: .photo-sorter/config.json in the directory)
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+    /// Walks sub directories recursively
+    #[clap(short = 'R', long, default_value = "false")]
+    pub recursive: bool,
+    /// Max depth of sub directories to walk in recursive mode
+    #[clap(long, requires = "recursive")]
+    pub max_depth: Option<usize>,
+    /// Sorting unit in recursive mode
+    #[clap(long, value_enum, default_value = "dir", requires = "recursive")]
+    pub scope: Scope,
+    /// Follows symbolic links (skipped by default)
+    #[clap(long, default_value = "false")]
+    pub follow_symlinks: bool,
+    /// Includes hidden directories in recursive mode (skipped by default)
+    #[clap(long, default_value = "false", requires = "recursive")]
+    pub hidden: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Computes the clock offset of a camera and saves it to the config
+    Align(AlignArgs),
+    /// Plans sorting without renaming, and shows or saves the plan
+    Plan(Box<PlanArgs>),
+    /// Applies a plan saved by `plan`, unless its files are changed since
+    Apply(ApplyArgs),
+}
+
+#[derive(clap::Args)]
+pub struct PlanArgs {
+    #[clap(flatten)]
+    pub sort: SortArgs,
+    /// JSON file to save the plan into, to review, edit, and `apply` it later (default: shows the plan)
+    #[clap(short, long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct ApplyArgs {
+    /// JSON file saved by `plan`
+    pub plan: PathBuf,
+}
+
+#[derive(clap::Args)]
+#[clap(group(ArgGroup::new("by").required(true).args(["reference", "gps"])))]
+pub struct AlignArgs {
+    /// Photo taken by the camera to be corrected
+    pub photo: PathBuf,
+    /// Photo taken at the same moment by a camera whose clock is right
+    #[clap(long)]
+    pub reference: Option<PathBuf>,
+    /// Estimates the offset from GPS time (UTC) of the photo
+    #[clap(long, default_value = "false")]
+    pub gps: bool,
+    /// Time zone the camera clock was set to, with --gps (e.g. `+09:00`, default: OffsetTimeOriginal or local time zone)
+    #[clap(long, requires = "gps")]
+    pub zone: Option<FixedOffset>,
+    /// Config file to save the offset (default: the nearest .photo-sorter/config.json above the photo, or in its directory)
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+    /// Directory to be sorted, into whose .photo-sorter/config.json the offset is saved
+    #[clap(long, conflicts_with = "config")]
+    pub dir: Option<PathBuf>,
+    /// Test mode that only shows the offset
+    #[clap(short, long, default_value = "false")]
+    pub test: bool,
+}
+
+impl SortArgs {
+    /// Directory to sort. Required by clap unless a subcommand is given.
+    pub fn dir(&self) -> &Path {
+        self.dir.as_ref().expect("directory is required").as_ref()
+    }
+
+    /// Which files are listed.
+    pub fn scan_options(&self) -> ScanOptions {
+        let max_depth = if self.recursive {
+            self.max_depth.unwrap_or(usize::MAX)
+        } else {
+            1
+        };
+        ScanOptions {
+            extensions: extensions(&self.ext),
+            magic: self.magic,
+            max_depth,
+            follow_symlinks: self.follow_symlinks,
+            hidden: self.hidden,
+        }
+    }
+
+    /// How files are sorted and named. Loads the config of camera clock offsets.
+    pub fn sort_options(&self) -> photo_sorter::Result<SortOptions> {
+        let mut patterns = self.filename_pattern.clone();
+        patterns.extend(FilenamePattern::builtin());
+        let transfer = if self.hardlink {
+            Transfer::Link
+        } else if self.copy || self.output.is_some() {
+            Transfer::Copy
+        } else {
+            Transfer::Move
+        };
+        let similar = self.similar.map(|algorithm| Similarity {
+            algorithm,
+            max_distance: self.similar_distance,
+            keep_best: self.keep_best,
+        });
+
+        Ok(SortOptions {
+            root: PathBuf::from(self.dir()),
+            delim: String::from(self.delim.as_ref()),
+            template: self.template(),
+            layout: self.layout.clone(),
+            output: self.output.clone(),
+            transfer,
+            reader: TimeReader::new(&self.time_source, &patterns),
+            config: Config::load(&self.config_path())?,
+            jobs: self.jobs(),
+            by_dir: self.recursive && self.scope == Scope::Dir,
+            group: !self.no_group,
+            desc: self.desc,
+            event_gap: self.event_gap,
+            bursts: self.bursts,
+            burst_folders: self.burst_folders,
+            duplicates: self.duplicates,
+            similar,
+            progress: Some(Box::new(print_progress)),
+        })
+    }
+
+    /// Template of the names. Events are in the default names unless they have their own folders.
+    pub fn template(&self) -> Template {
+        let events = self.event_gap.is_some()
+            && !self
+                .layout
+                .as_ref()
+                .is_some_and(|layout| layout.uses_event());
+        self.template
+            .clone()
+            .unwrap_or_else(|| Template::prefix(self.delim.as_ref(), events))
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.config
+            .clone()
+            .unwrap_or_else(|| Config::find(self.dir()))
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1)
+    }
 }
 
 impl FromStr for DirPath {
@@ -65,4 +319,4 @@ impl AsRef<str> for Delim {
     fn as_ref(&self) -> &str {
         &self.0
     }
-}
\ No newline at end of file
+}
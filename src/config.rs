@@ -0,0 +1,227 @@
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    error::{invalid, Error, Result},
+    journal::DATA_DIR,
+    metadata::{Camera, Photo},
+};
+
+const CONFIG_FILE: &str = "config.json";
+
+/// Settings kept in a sorted directory.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Clock offsets of cameras.
+    #[serde(default)]
+    pub cameras: Vec<CameraOffset>,
+}
+
+/// Offset added to the times of a camera whose clock was wrong.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CameraOffset {
+    /// `Make Model` or `Model`, e.g. `Canon EOS R6`. Compared ignoring case.
+    pub model: String,
+    /// Body serial number, to tell apart cameras of the same model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    pub offset: ClockOffset,
+}
+
+/// Signed duration in `+HH:MM:SS` form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClockOffset(pub TimeDelta);
+
+impl Config {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(DATA_DIR).join(CONFIG_FILE)
+    }
+
+    /// Config file of the directory: the nearest existing one in the directory or its parents,
+    /// or a new one in the directory.
+    pub fn find(dir: &Path) -> PathBuf {
+        let absolute = std::path::absolute(dir).unwrap_or_else(|_| PathBuf::from(dir));
+        absolute
+            .ancestors()
+            .map(Self::path)
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| Self::path(dir))
+    }
+
+    /// Loads the config file. Returns the default config if there is no file.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path).map_err(Error::io("read config", path))?;
+        serde_json::from_str(&text).map_err(|error| Error::Broken {
+            kind: "config",
+            path: PathBuf::from(path),
+            error,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(Error::io("create directory", dir))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|error| Error::Broken {
+            kind: "config",
+            path: PathBuf::from(path),
+            error,
+        })?;
+        fs::write(path, text).map_err(Error::io("write config", path))
+    }
+
+    /// Finds the clock offset of the camera. Entries with a serial number are preferred.
+    pub fn clock_offset(&self, camera: &Camera) -> Option<ClockOffset> {
+        let matches = |entry: &&CameraOffset| camera.is(&entry.model);
+        let by_serial = self.cameras.iter().filter(matches).find(|entry| {
+            entry.serial.is_some() && entry.serial.as_deref() == camera.serial.as_deref()
+        });
+        let by_model = self
+            .cameras
+            .iter()
+            .filter(matches)
+            .find(|entry| entry.serial.is_none());
+        by_serial.or(by_model).map(|entry| entry.offset)
+    }
+
+    /// Adds the clock offset of the camera to the capture time of the photo.
+    pub fn correct(&self, photo: &mut Photo) {
+        if let (Some(timestamp), Some(offset)) =
+            (photo.timestamp.as_mut(), self.clock_offset(&photo.camera))
+        {
+            timestamp.correct(offset);
+        }
+    }
+
+    /// Sets the clock offset of the camera, replacing the existing one.
+    pub fn set_clock_offset(&mut self, model: String, serial: Option<String>, offset: ClockOffset) {
+        self.cameras
+            .retain(|entry| !(entry.model.eq_ignore_ascii_case(&model) && entry.serial == serial));
+        self.cameras.push(CameraOffset {
+            model,
+            serial,
+            offset,
+        });
+    }
+}
+
+impl fmt::Display for ClockOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < TimeDelta::zero() { '-' } else { '+' };
+        let seconds = self.0.num_seconds().abs();
+        write!(
+            f,
+            "{sign}{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+impl FromStr for ClockOffset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sign, rest) = match s.trim().split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => invalid!("Offset {s} needs to start with + or -."),
+        };
+        let parts: Vec<i64> = rest
+            .split(':')
+            .map(|part| part.parse::<i64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| Error::Invalid(format!("Offset {s} is not in +HH:MM:SS form.")))?;
+        let [hours, minutes, seconds] = parts[..] else {
+            invalid!("Offset {s} is not in +HH:MM:SS form.");
+        };
+        if parts.iter().any(|part| *part < 0) || minutes >= 60 || seconds >= 60 {
+            invalid!("Offset {s} is not in +HH:MM:SS form.");
+        }
+
+        hours
+            .checked_mul(3600)
+            .and_then(|total| total.checked_add(minutes * 60 + seconds))
+            .and_then(|total| TimeDelta::try_seconds(sign * total))
+            .map(Self)
+            .ok_or_else(|| Error::Invalid(format!("Offset {s} is out of range.")))
+    }
+}
+
+impl TryFrom<String> for ClockOffset {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ClockOffset> for String {
+    fn from(value: ClockOffset) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clock_offset() {
+        let offset: ClockOffset = "+00:03:12".parse().unwrap();
+        assert_eq!(offset.0, TimeDelta::seconds(192));
+        let offset: ClockOffset = "-25:00:01".parse().unwrap();
+        assert_eq!(offset.to_string(), "-25:00:01");
+        assert!("00:03:12".parse::<ClockOffset>().is_err());
+        assert!("+00:60:00".parse::<ClockOffset>().is_err());
+        assert!("+9223372036854775807:00:00".parse::<ClockOffset>().is_err());
+        assert!("-3000000000000:00:00".parse::<ClockOffset>().is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let sub = root.join("2024").join("trip");
+        fs::create_dir_all(&sub).unwrap();
+        assert_eq!(Config::find(&sub), Config::path(&sub));
+
+        Config::default().save(&Config::path(root)).unwrap();
+        assert_eq!(Config::find(&sub), Config::path(root));
+        assert_eq!(Config::find(root), Config::path(root));
+    }
+
+    #[test]
+    fn test_camera_match() {
+        let text = r#"{"cameras": [
+            {"model": "Canon EOS R6", "offset": "+00:03:12"},
+            {"model": "canon eos r6", "serial": "042", "offset": "-00:00:05"}
+        ]}"#;
+        let config: Config = serde_json::from_str(text).unwrap();
+        let mut camera = Camera {
+            make: Some(String::from("Canon")),
+            model: Some(String::from("Canon EOS R6")),
+            serial: Some(String::from("001")),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.clock_offset(&camera).map(|o| o.to_string()),
+            Some(String::from("+00:03:12"))
+        );
+        camera.serial = Some(String::from("042"));
+        assert_eq!(
+            config.clock_offset(&camera).map(|o| o.to_string()),
+            Some(String::from("-00:00:05"))
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use clap::ValueEnum;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::{
+    checksum::sha256,
+    error::{Error, Result},
+};
+
+/// Folder that duplicates are moved into.
+pub const DUPLICATES_DIR: &str = "duplicates";
+
+/// What to do with files whose content is the same as an earlier file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DuplicateMode {
+    /// Only reports duplicates, which are numbered as usual
+    Report,
+    /// Leaves duplicates as is, out of the numbering
+    Skip,
+    /// Moves duplicates into the `duplicates` folder
+    Move,
+    /// Replaces duplicates with hard links to the original, out of the numbering
+    Link,
+}
+
+/// File whose content is the same as the original, which comes first in the list.
+pub struct Duplicate {
+    pub path: PathBuf,
+    pub original: PathBuf,
+}
+
+/// Finds byte-identical files. Only files of the same size are hashed.
+pub fn find_duplicates(files: &[PathBuf]) -> Result<Vec<Duplicate>> {
+    let sizes = files
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .map(|metadata| metadata.len())
+                .map_err(Error::io("read", file))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for len in sizes.iter() {
+        *counts.entry(*len).or_default() += 1;
+    }
+
+    let mut originals: HashMap<String, &PathBuf> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (file, len) in files.iter().zip(sizes) {
+        if counts[&len] < 2 {
+            continue;
+        }
+        let hash = sha256(file).map_err(Error::io("read", file))?;
+        match originals.get(&hash) {
+            Some(original) => duplicates.push(Duplicate {
+                path: file.clone(),
+                original: PathBuf::from(original),
+            }),
+            None => {
+                originals.insert(hash, file);
+            }
+        }
+    }
+    Ok(duplicates)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.jpg", "b.jpg", "c.jpg", "d.jpg"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        for (file, content) in files.iter().zip(["photo", "other", "photo", "x"]) {
+            fs::write(file, content).unwrap();
+        }
+
+        let duplicates = find_duplicates(&files).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].path, files[2]);
+        assert_eq!(duplicates[0].original, files[0]);
+    }
+}
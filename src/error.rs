@@ -0,0 +1,75 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Errors of sorting photos.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A file or a directory can not be read or written.
+    #[error("Failed to {action} {}", .path.to_string_lossy())]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A journal or a config file is not in its format.
+    #[error("Broken {kind} {}: {error}", .path.to_string_lossy())]
+    Broken {
+        kind: &'static str,
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    /// An option, e.g. a template or a clock offset, is not valid.
+    #[error("{0}")]
+    Invalid(String),
+    /// The plan would overwrite files, so that nothing is done.
+    #[error("Nothing is renamed.\n{}", .0.join("\n"))]
+    Conflicts(Vec<String>),
+    /// Files of a saved plan were changed after it was saved.
+    #[error("The plan is out of date, and nothing is renamed.\n{}", .0.join("\n"))]
+    Stale(Vec<String>),
+    /// Renaming failed, and some files could not be moved back.
+    #[error("{error}\n{}", .failures.join("\n"))]
+    Rollback {
+        error: Box<Error>,
+        failures: Vec<String>,
+    },
+    /// Files are renamed and recorded, but some copies are not removed
+    /// or some duplicates are not replaced with links.
+    #[error("Files are renamed, but not everything is done.\n{}", .0.join("\n"))]
+    Incomplete(Vec<String>),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Makes `Error::Io` of the path, for `map_err`.
+    pub(crate) fn io(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        let path = PathBuf::from(path);
+        move |source| Self::Io {
+            action,
+            path,
+            source,
+        }
+    }
+
+    /// Adds the failures of the rollback to the error that caused it.
+    pub(crate) fn rollback(error: Self, failures: Vec<String>) -> Self {
+        if failures.is_empty() {
+            return error;
+        }
+        Self::Rollback {
+            error: Box::new(error),
+            failures,
+        }
+    }
+}
+
+/// Returns `Error::Invalid` with the formatted message, like `anyhow::bail!`.
+macro_rules! invalid {
+    ($($arg:tt)*) => {
+        return Err($crate::error::Error::Invalid(format!($($arg)*)))
+    };
+}
+pub(crate) use invalid;
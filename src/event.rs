@@ -0,0 +1,119 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use std::str::FromStr;
+
+use crate::{
+    error::{invalid, Error},
+    group::Item,
+};
+
+/// Gap between capture times that starts a new event, e.g. `3h`, `90m` or `1h30m`.
+#[derive(Clone, Copy, Debug)]
+pub struct EventGap(pub TimeDelta);
+
+impl FromStr for EventGap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut gap = TimeDelta::zero();
+        let mut digits = String::new();
+        for c in s.trim().chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+            let Ok(n) = digits.parse::<i64>() else {
+                invalid!("Gap {s} is not like 3h, 90m or 1h30m.");
+            };
+            let part = match c {
+                'd' => TimeDelta::try_days(n),
+                'h' => TimeDelta::try_hours(n),
+                'm' => TimeDelta::try_minutes(n),
+                's' => TimeDelta::try_seconds(n),
+                _ => invalid!("Unit {c} of {s} is not one of d, h, m and s."),
+            };
+            let Some(sum) = part.and_then(|part| gap.checked_add(&part)) else {
+                invalid!("Gap {s} is too long.");
+            };
+            gap = sum;
+            digits.clear();
+        }
+        if !digits.is_empty() || gap <= TimeDelta::zero() {
+            invalid!("Gap {s} is not like 3h, 90m or 1h30m.");
+        }
+
+        Ok(Self(gap))
+    }
+}
+
+/// Numbers events of sorted items from 1.
+///
+/// A new event starts where consecutive capture times are further apart than `gap`.
+/// Items without capture time make an event of their own.
+pub fn number_events(items: &[Item], gap: TimeDelta) -> Vec<usize> {
+    let mut events = Vec::with_capacity(items.len());
+    let mut event = 0;
+    let mut last: Option<NaiveDateTime> = None;
+    for (index, item) in items.iter().enumerate() {
+        let time = item.timestamp().map(|timestamp| timestamp.time.instant());
+        let split = match (last, time) {
+            (Some(last), Some(time)) => (time - last).abs() > gap,
+            (None, None) => false,
+            _ => true,
+        };
+        if index == 0 || split {
+            event += 1;
+        }
+        events.push(event);
+        last = time;
+    }
+    events
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        metadata::Photo,
+        timestamp::{CaptureTime, Timestamp},
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_number_events() {
+        assert_eq!(
+            "1h30m".parse::<EventGap>().unwrap().0,
+            TimeDelta::minutes(90)
+        );
+        assert!("3".parse::<EventGap>().is_err());
+        assert!("3w".parse::<EventGap>().is_err());
+        assert!("999999999999d".parse::<EventGap>().is_err());
+        assert!("2000000000000h2000000000000h".parse::<EventGap>().is_err());
+
+        let times = [
+            Some("2024:05:01 10:00:00"),
+            Some("2024:05:01 12:59:00"),
+            Some("2024:05:01 16:00:00"),
+            None,
+        ];
+        let items: Vec<Item> = times
+            .iter()
+            .map(|time| {
+                let timestamp = time.map(|time| Timestamp {
+                    time: CaptureTime::from_exif(time, None, None).unwrap(),
+                    source: "exif-original",
+                    camera_clock: true,
+                    correction: None,
+                });
+                Item {
+                    photos: vec![Photo {
+                        path: PathBuf::from("a.jpg"),
+                        timestamp,
+                        ..Default::default()
+                    }],
+                    sidecars: Vec::new(),
+                }
+            })
+            .collect();
+        assert_eq!(number_events(&items, TimeDelta::hours(3)), vec![1, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,206 @@
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use std::str::FromStr;
+
+use crate::{
+    error::{invalid, Error},
+    timestamp::CaptureTime,
+};
+
+/// Patterns of common phones and messengers, tried after user patterns.
+const BUILTIN_PATTERNS: &[&str] = &[
+    // IMG_20230514_101522.jpg, PXL_20230514_101522123.jpg
+    "%Y%m%d_%H%M%S%f",
+    // VID-20230514-101522.mp4
+    "%Y%m%d-%H%M%S%f",
+    // Screenshot_2023-05-14-10-15-22.png
+    "%Y-%m-%d-%H-%M-%S",
+    // Screenshot_2023-05-14_10-15-22.png
+    "%Y-%m-%d_%H-%M-%S",
+    // 2023-05-14 10.15.22.jpg
+    "%Y-%m-%d %H.%M.%S",
+    // IMG-20230514-WA0003.jpg
+    "%Y%m%d-WA",
+];
+
+/// Pattern to find the capture time in a file name, in `strftime` like syntax.
+///
+/// Supported fields are `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%f` (optional sub-second digits)
+/// and `%%`. Other characters are matched literally. The pattern may match anywhere in the name.
+#[derive(Clone, Debug)]
+pub struct FilenamePattern(Vec<Token>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Fraction,
+}
+
+#[derive(Default)]
+struct Fields {
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    nanos: i64,
+}
+
+impl FilenamePattern {
+    pub fn builtin() -> Vec<Self> {
+        BUILTIN_PATTERNS
+            .iter()
+            .map(|pattern| pattern.parse().unwrap())
+            .collect()
+    }
+
+    /// Finds the first match in `name`.
+    pub fn find(&self, name: &str) -> Option<CaptureTime> {
+        let chars: Vec<char> = name.chars().collect();
+        (0..chars.len())
+            // Does not start matching in the middle of a number.
+            .filter(|&start| start == 0 || !chars[start - 1].is_ascii_digit())
+            .find_map(|start| self.match_at(&chars[start..]))
+    }
+
+    fn match_at(&self, chars: &[char]) -> Option<CaptureTime> {
+        let mut fields = Fields::default();
+        let mut pos = 0;
+        for token in self.0.iter() {
+            let width = match token {
+                Token::Literal(c) => {
+                    if chars.get(pos) != Some(c) {
+                        return None;
+                    }
+                    pos += 1;
+                    continue;
+                }
+                Token::Year => 4,
+                Token::Fraction => chars[pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count()
+                    .min(9),
+                _ => 2,
+            };
+            let digits: String = chars.get(pos..pos + width)?.iter().collect();
+            if !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            pos += width;
+
+            match token {
+                Token::Year => fields.year = digits.parse().ok()?,
+                Token::Month => fields.month = digits.parse().ok()?,
+                Token::Day => fields.day = digits.parse().ok()?,
+                Token::Hour => fields.hour = digits.parse().ok()?,
+                Token::Minute => fields.minute = digits.parse().ok()?,
+                Token::Second => fields.second = digits.parse().ok()?,
+                Token::Fraction if !digits.is_empty() => {
+                    fields.nanos = digits.parse::<i64>().ok()? * 10_i64.pow(9 - width as u32)
+                }
+                _ => {}
+            }
+        }
+        // Does not end matching in the middle of a number either.
+        let ends_with_number = self
+            .0
+            .last()
+            .is_some_and(|token| !matches!(token, Token::Literal(_) | Token::Fraction));
+        if ends_with_number && chars.get(pos).is_some_and(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let local: NaiveDateTime = NaiveDate::from_ymd_opt(fields.year, fields.month, fields.day)?
+            .and_hms_opt(fields.hour, fields.minute, fields.second)?;
+        Some(CaptureTime {
+            local: local + TimeDelta::nanoseconds(fields.nanos),
+            offset: None,
+        })
+    }
+}
+
+impl FromStr for FilenamePattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Vec::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                tokens.push(Token::Literal(c));
+                continue;
+            }
+            let token = match chars.next() {
+                Some('Y') => Token::Year,
+                Some('m') => Token::Month,
+                Some('d') => Token::Day,
+                Some('H') => Token::Hour,
+                Some('M') => Token::Minute,
+                Some('S') => Token::Second,
+                Some('f') => Token::Fraction,
+                Some('%') => Token::Literal('%'),
+                Some(c) => invalid!("Unknown field %{c} in pattern {s}."),
+                None => invalid!("Pattern {s} ends with %."),
+            };
+            tokens.push(token);
+        }
+
+        for required in [Token::Year, Token::Month, Token::Day] {
+            if !tokens.contains(&required) {
+                invalid!("Pattern {s} needs %Y, %m and %d.");
+            }
+        }
+
+        Ok(Self(tokens))
+    }
+}
+
+/// Finds the capture time in `name` by the first pattern that matches.
+pub fn filename_time(name: &str, patterns: &[FilenamePattern]) -> Option<CaptureTime> {
+    patterns.iter().find_map(|pattern| pattern.find(name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn time(name: &str) -> Option<String> {
+        filename_time(name, &FilenamePattern::builtin())
+            .map(|t| t.local.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+    }
+
+    #[test]
+    fn test_builtin_patterns() {
+        let expected = Some(String::from("2023-05-14 10:15:22.000"));
+        assert_eq!(time("IMG_20230514_101522.jpg"), expected);
+        assert_eq!(time("Screenshot_2023-05-14-10-15-22.png"), expected);
+        assert_eq!(time("2023-05-14 10.15.22.jpg"), expected);
+        assert_eq!(
+            time("PXL_20230514_101522123.jpg"),
+            Some(String::from("2023-05-14 10:15:22.123"))
+        );
+        assert_eq!(
+            time("IMG-20230514-WA0003.jpg"),
+            Some(String::from("2023-05-14 00:00:00.000"))
+        );
+        assert_eq!(time("IMG_1234.jpg"), None);
+        assert_eq!(time("IMG_20231399_101522.jpg"), None);
+    }
+
+    #[test]
+    fn test_user_pattern() {
+        let pattern: FilenamePattern = "DSC%Y.%m.%d".parse().unwrap();
+        let actual = filename_time("DSC2021.12.31.jpg", &[pattern]).map(|t| t.local.date());
+        assert_eq!(actual, NaiveDate::from_ymd_opt(2021, 12, 31));
+
+        assert!("%H%M%S".parse::<FilenamePattern>().is_err());
+        assert!("%Y%m%d%x".parse::<FilenamePattern>().is_err());
+    }
+}
@@ -0,0 +1,551 @@
+use exif::{Exif, In, Tag};
+use std::{
+    fs,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{
+    error::{invalid, Error},
+    video::{boxes, read_moov},
+};
+
+/// Extensions of images sorted by default, in lower case.
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "heic", "heif", "png", "webp", "tif", "tiff", "avif",
+];
+/// Extensions of camera RAW sorted by default, in lower case.
+pub const RAW_EXTENSIONS: &[&str] = &["cr2", "cr3", "nef", "arw", "dng", "raf", "orf"];
+/// Extensions of videos sorted by default, in lower case.
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v", "3gp"];
+
+/// Embedded JPEG of RAF larger than this is not read.
+const MAX_RAF_JPEG_SIZE: u32 = 64 * 1024 * 1024;
+/// Metadata of TIFF based files spanning more than this is not read.
+const MAX_TIFF_METADATA_SIZE: u64 = 16 * 1024 * 1024;
+/// Tags of IFDs under IFD0: Exif, GPS and Interoperability.
+const CHILD_IFD_TAGS: &[u16] = &[0x8769, 0x8825, 0xa005];
+/// Tags of the offset and the length of the JPEG thumbnail in IFD1.
+const THUMBNAIL_TAGS: (u16, u16) = (0x0201, 0x0202);
+/// UUID of the box that holds Exif in Canon CR3.
+const CR3_UUID: [u8; 16] = [
+    0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+
+/// Change to the default extensions, `png` or `+png` to add and `-png` to remove.
+#[derive(Clone)]
+pub enum ExtChange {
+    Add(String),
+    Remove(String),
+}
+
+/// File format found by the leading bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    WebP,
+    /// TIFF and TIFF based RAW (CR2, NEF, ARW, DNG)
+    Tiff,
+    /// Olympus ORF and Panasonic RW2, TIFF with own signature
+    TiffVariant,
+    Raf,
+    Cr3,
+    /// HEIF and AVIF
+    Heif,
+    /// MP4 and QuickTime
+    Video,
+}
+
+impl FromStr for ExtChange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (remove, ext) = match s.strip_prefix('-') {
+            Some(ext) => (true, ext),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let ext = ext.trim_start_matches('.').to_ascii_lowercase();
+        if ext.is_empty() {
+            invalid!("Extension is empty.");
+        }
+
+        Ok(if remove {
+            Self::Remove(ext)
+        } else {
+            Self::Add(ext)
+        })
+    }
+}
+
+/// Applies the changes to the default extensions.
+pub fn extensions(changes: &[ExtChange]) -> Vec<String> {
+    let mut extensions: Vec<String> = [IMAGE_EXTENSIONS, RAW_EXTENSIONS, VIDEO_EXTENSIONS]
+        .concat()
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect();
+    for change in changes.iter() {
+        match change {
+            ExtChange::Add(ext) if !extensions.contains(ext) => extensions.push(ext.clone()),
+            ExtChange::Remove(ext) => extensions.retain(|e| e != ext),
+            _ => {}
+        }
+    }
+    extensions
+}
+
+impl Format {
+    /// Detects the format by the magic bytes of the file.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let mut head = [0; 32];
+        let mut file = fs::File::open(path).ok()?;
+        let len = file.read(&mut head).ok()?;
+        Self::from_bytes(&head[..len])
+    }
+
+    fn from_bytes(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0xff, 0xd8, 0xff]) {
+            return Some(Self::Jpeg);
+        }
+        if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Some(Self::Png);
+        }
+        if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+            return Some(Self::WebP);
+        }
+        if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+            return Some(Self::Tiff);
+        }
+        if head.starts_with(b"IIRO") || head.starts_with(b"IIRS") || head.starts_with(b"IIU\0") {
+            return Some(Self::TiffVariant);
+        }
+        if head.starts_with(b"FUJIFILMCCD-RAW") {
+            return Some(Self::Raf);
+        }
+        if head.get(4..8) == Some(b"ftyp") {
+            let brands = head.get(8..).unwrap_or_default();
+            let has = |brand: &[u8]| brands.chunks(4).any(|b| b == brand);
+            if has(b"crx ") {
+                return Some(Self::Cr3);
+            }
+            if has(b"mif1") || has(b"msf1") || has(b"heic") || has(b"avif") {
+                return Some(Self::Heif);
+            }
+            return Some(Self::Video);
+        }
+        None
+    }
+}
+
+/// Reads Exif of the file. CR3 has two Exif blocks, so that all of them are returned.
+pub fn read_exif(path: &Path) -> Vec<Exif> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+    let mut reader = BufReader::new(file);
+    let mut head = Vec::new();
+    if (&mut reader).take(32).read_to_end(&mut head).is_err()
+        || reader.seek(SeekFrom::Start(0)).is_err()
+    {
+        return Vec::new();
+    }
+
+    match Format::from_bytes(&head) {
+        Some(Format::Tiff | Format::TiffVariant) => read_tiff(&mut reader).into_iter().collect(),
+        Some(Format::Raf) => read_raf(&mut reader).into_iter().collect(),
+        Some(Format::Cr3) => read_cr3(&mut reader),
+        Some(Format::Video) => Vec::new(),
+        _ => exif::Reader::new()
+            .read_from_container(&mut reader)
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Reads the ASCII field of the primary image.
+///
+/// Fields are found by tag number, because tags in `CMT2` of CR3 are not in Exif IFD.
+pub fn exif_ascii(exif: &[Exif], tag: Tag) -> Option<String> {
+    let field = exif
+        .iter()
+        .flat_map(|exif| exif.fields())
+        .find(|field| field.tag.number() == tag.number() && field.ifd_num == In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Ascii(values) => values.first().map(|value| {
+            String::from_utf8_lossy(value)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string()
+        }),
+        _ => None,
+    }
+}
+
+/// Entries of an IFD, and its link to the next IFD.
+struct Ifd {
+    /// Tag, type, count, and the value or the offset of the value.
+    entries: Vec<(u16, u16, u64, u64)>,
+    /// Position of the link.
+    link: u64,
+    /// Offset of the next IFD, 0 if none.
+    next: u64,
+}
+
+impl Ifd {
+    fn read<R: Read + Seek>(reader: &mut R, offset: u64, big_endian: bool) -> Option<Self> {
+        reader.seek(SeekFrom::Start(offset)).ok()?;
+        let mut count = [0; 2];
+        reader.read_exact(&mut count).ok()?;
+        let mut data = vec![0; u16_from(&count, big_endian) as usize * 12 + 4];
+        reader.read_exact(&mut data).ok()?;
+
+        let (entries, link) = data.split_at(data.len() - 4);
+        Some(Self {
+            entries: entries
+                .chunks(12)
+                .map(|entry| {
+                    (
+                        u16_from(&entry[0..2], big_endian),
+                        u16_from(&entry[2..4], big_endian),
+                        u32_from(&entry[4..8], big_endian) as u64,
+                        u32_from(&entry[8..12], big_endian) as u64,
+                    )
+                })
+                .collect(),
+            link: offset + 2 + entries.len() as u64,
+            next: u32_from(link, big_endian) as u64,
+        })
+    }
+
+    /// End of the IFD and the values it points to.
+    fn end(&self) -> u64 {
+        self.entries
+            .iter()
+            .fold(self.link + 4, |end, &(_, kind, count, value)| {
+                let len = type_size(kind) * count;
+                if len > 4 {
+                    end.max(value + len)
+                } else {
+                    end
+                }
+            })
+    }
+
+    /// Value of the `LONG` field.
+    fn value(&self, tag: u16) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|&&(t, kind, count, _)| t == tag && kind == 4 && count == 1)
+            .map(|&(_, _, _, value)| value)
+    }
+
+    /// Offsets of the child IFDs.
+    fn children(&self) -> impl Iterator<Item = u64> + '_ {
+        CHILD_IFD_TAGS.iter().filter_map(|&tag| self.value(tag))
+    }
+}
+
+/// Reads TIFF and TIFF based RAW. The signature of ORF and RW2 is replaced with the one of TIFF.
+///
+/// Only the leading part of the file that holds IFD0, its child IFDs and their values is read,
+/// with IFD1 and its thumbnail if they fit in it. Links to later IFDs (e.g. previews) are cut off.
+fn read_tiff<R: Read + Seek>(reader: &mut R) -> Option<Exif> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).ok()?;
+    let big_endian = match &header[0..2] {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+
+    let ifd0 = Ifd::read(
+        reader,
+        u32_from(&header[4..8], big_endian) as u64,
+        big_endian,
+    )?;
+    let mut end = ifd0.end();
+    let mut pending: Vec<u64> = ifd0.children().collect();
+    // A few IFDs at most, so that broken pointers can not loop.
+    for _ in 0..3 {
+        let Some(offset) = pending.pop() else {
+            break;
+        };
+        let ifd = Ifd::read(reader, offset, big_endian)?;
+        end = end.max(ifd.end());
+        pending.extend(ifd.children());
+    }
+
+    let thumbnail = match ifd0.next {
+        0 => None,
+        offset => Ifd::read(reader, offset, big_endian),
+    }
+    .map(|ifd1| {
+        let end = match (ifd1.value(THUMBNAIL_TAGS.0), ifd1.value(THUMBNAIL_TAGS.1)) {
+            (Some(offset), Some(len)) => ifd1.end().max(offset + len),
+            _ => ifd1.end(),
+        };
+        (ifd1.link, end)
+    })
+    .filter(|&(_, thumbnail_end)| thumbnail_end <= MAX_TIFF_METADATA_SIZE);
+    let cut = match thumbnail {
+        Some((link, thumbnail_end)) => {
+            end = end.max(thumbnail_end);
+            link
+        }
+        None => ifd0.link,
+    };
+    if end > MAX_TIFF_METADATA_SIZE {
+        return None;
+    }
+
+    let mut data = vec![0; end as usize];
+    reader.seek(SeekFrom::Start(0)).ok()?;
+    reader.read_exact(&mut data).ok()?;
+    data[0..4].copy_from_slice(if big_endian { b"MM\0*" } else { b"II*\0" });
+    data[cut as usize..cut as usize + 4].fill(0);
+    exif::Reader::new().read_raw(data).ok()
+}
+
+fn u16_from(bytes: &[u8], big_endian: bool) -> u16 {
+    let bytes = bytes.try_into().unwrap();
+    match big_endian {
+        true => u16::from_be_bytes(bytes),
+        false => u16::from_le_bytes(bytes),
+    }
+}
+
+fn u32_from(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = bytes.try_into().unwrap();
+    match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    }
+}
+
+/// Size of a value of the TIFF field type. Unknown types have no size.
+fn type_size(kind: u16) -> u64 {
+    match kind {
+        1 | 2 | 6 | 7 => 1,
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => 0,
+    }
+}
+
+/// Reads the embedded JPEG of Fujifilm RAF.
+fn read_raf<R: Read + Seek>(reader: &mut R) -> Option<Exif> {
+    let mut header = [0; 92];
+    reader.read_exact(&mut header).ok()?;
+    let offset = u32::from_be_bytes(header[84..88].try_into().unwrap());
+    let len = u32::from_be_bytes(header[88..92].try_into().unwrap());
+    if len > MAX_RAF_JPEG_SIZE {
+        return None;
+    }
+
+    reader.seek(SeekFrom::Start(offset as u64)).ok()?;
+    let mut jpeg = vec![0; len as usize];
+    reader.read_exact(&mut jpeg).ok()?;
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(jpeg))
+        .ok()
+}
+
+/// Reads `CMT1` (IFD0) and `CMT2` (Exif IFD) of Canon CR3.
+fn read_cr3<R: Read + Seek>(reader: &mut R) -> Vec<Exif> {
+    let Some(moov) = read_moov(reader) else {
+        return Vec::new();
+    };
+    let Some(uuid) = boxes(&moov)
+        .find(|(kind, content)| *kind == b"uuid" && content.starts_with(&CR3_UUID))
+        .map(|(_, content)| &content[CR3_UUID.len()..])
+    else {
+        return Vec::new();
+    };
+
+    boxes(uuid)
+        .filter(|(kind, _)| *kind == b"CMT1" || *kind == b"CMT2")
+        .filter_map(|(_, content)| exif::Reader::new().read_raw(content.to_vec()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::video::make_box;
+
+    #[test]
+    fn test_extensions() {
+        let changes: Vec<ExtChange> = ["-mov", "+.GIF", "png"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let actual = extensions(&changes);
+        assert!(!actual.contains(&String::from("mov")));
+        assert!(actual.contains(&String::from("gif")));
+        assert_eq!(actual.iter().filter(|ext| *ext == "png").count(), 1);
+    }
+
+    #[test]
+    fn test_format_from_bytes() {
+        assert_eq!(
+            Format::from_bytes(b"IIRO\x08\0\0\0"),
+            Some(Format::TiffVariant)
+        );
+        assert_eq!(
+            Format::from_bytes(b"\0\0\0\x18ftypcrx \0\0\0\x01crx isom"),
+            Some(Format::Cr3)
+        );
+        assert_eq!(
+            Format::from_bytes(b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf"),
+            Some(Format::Heif)
+        );
+        assert_eq!(
+            Format::from_bytes(b"\0\0\0\x14ftypqt  \0\0\0\0qt  "),
+            Some(Format::Video)
+        );
+        assert_eq!(Format::from_bytes(b"GIF89a"), None);
+    }
+
+    /// TIFF with ASCII fields in IFD0 and in Exif IFD, and IFD1 if the thumbnail is not empty.
+    fn make_tiff(
+        big_endian: bool,
+        ifd0: &[(u16, &str)],
+        exif: &[(u16, &str)],
+        thumbnail: &[u8],
+    ) -> Vec<u8> {
+        let u16_bytes = |n: u16| match big_endian {
+            true => n.to_be_bytes(),
+            false => n.to_le_bytes(),
+        };
+        let u32_bytes = |n: usize| match big_endian {
+            true => (n as u32).to_be_bytes(),
+            false => (n as u32).to_le_bytes(),
+        };
+        let ascii = |fields: &[(u16, &str)]| -> Vec<(u16, u16, Vec<u8>)> {
+            fields
+                .iter()
+                .map(|(tag, text)| (*tag, 2, format!("{text}\0").into_bytes()))
+                .collect()
+        };
+        let long = |tag: u16, n: usize| (tag, 4, u32_bytes(n).to_vec());
+
+        // Header, IFD0, Exif IFD, IFD1, thumbnail, then values.
+        let ifd_len = |count: usize| 2 + 12 * count + 4;
+        let exif_offset = 8 + ifd_len(ifd0.len() + !exif.is_empty() as usize);
+        let ifd1_offset = exif_offset
+            + if exif.is_empty() {
+                0
+            } else {
+                ifd_len(exif.len())
+            };
+        let thumbnail_offset = ifd1_offset + if thumbnail.is_empty() { 0 } else { ifd_len(2) };
+        let values_offset = thumbnail_offset + thumbnail.len();
+
+        let mut ifds = vec![(ascii(ifd0), 0)];
+        if !exif.is_empty() {
+            ifds[0].0.push(long(0x8769, exif_offset));
+            ifds.push((ascii(exif), 0));
+        }
+        if !thumbnail.is_empty() {
+            ifds[0].1 = ifd1_offset;
+            ifds.push((
+                vec![
+                    long(0x0201, thumbnail_offset),
+                    long(0x0202, thumbnail.len()),
+                ],
+                0,
+            ));
+        }
+
+        let signature = if big_endian { b"MM\0*" } else { b"II*\0" };
+        let mut data = [signature.to_vec(), u32_bytes(8).to_vec()].concat();
+        let mut values = Vec::new();
+        for (entries, next) in ifds {
+            data.extend(u16_bytes(entries.len() as u16));
+            for (tag, kind, value) in entries {
+                data.extend(u16_bytes(tag));
+                data.extend(u16_bytes(kind));
+                data.extend(u32_bytes(value.len() / type_size(kind) as usize));
+                if value.len() <= 4 {
+                    data.extend(&value);
+                    data.extend(vec![0; 4 - value.len()]);
+                } else {
+                    data.extend(u32_bytes(values_offset + values.len()));
+                    values.extend(value);
+                }
+            }
+            data.extend(u32_bytes(next));
+        }
+        [data, thumbnail.to_vec(), values].concat()
+    }
+
+    #[test]
+    fn test_read_raw_exif() {
+        let dir = tempfile::tempdir().unwrap();
+        let read = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            let exif = read_exif(&path);
+            (
+                exif_ascii(&exif, Tag::Make),
+                exif_ascii(&exif, Tag::DateTimeOriginal),
+            )
+        };
+        let expected = (
+            Some(String::from("OLYMPUS")),
+            Some(String::from("2024:05:01 10:00:00")),
+        );
+        let (ifd0, exif) = ([(0x010f, "OLYMPUS")], [(0x9003, "2024:05:01 10:00:00")]);
+        let tiff = make_tiff(false, &ifd0, &exif, &[]);
+
+        // TIFF based RAW in big endian, whose IFD1 has the thumbnail and links to
+        // the next IFD far beyond the metadata, which is not read.
+        let thumbnail = [0xff, 0xd8, 0xff, 0xd9];
+        let mut nef = make_tiff(true, &ifd0, &exif, &thumbnail);
+        let link = 8 + (2 + 12 * 2 + 4) + (2 + 12 + 4) + 2 + 12 * 2;
+        nef[link..link + 4].copy_from_slice(&0x4000_0000u32.to_be_bytes());
+        nef.extend(vec![0; 4096]);
+        assert_eq!(read("a.nef", &nef), expected);
+        let nef = read_exif(&dir.path().join("a.nef"));
+        let len = nef[0].get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL);
+        assert_eq!(len.and_then(|field| field.value.get_uint(0)), Some(4));
+
+        // ORF and RW2 whose next IFD is far beyond the metadata, which is not read.
+        for signature in [b"IIRO", b"IIU\0"] {
+            let mut orf = tiff.clone();
+            orf[0..4].copy_from_slice(signature);
+            let link = 8 + 2 + 12 * 2;
+            orf[link..link + 4].copy_from_slice(&0x4000_0000u32.to_le_bytes());
+            orf.extend(vec![0; 4096]);
+            assert_eq!(read("a.orf", &orf), expected);
+        }
+
+        // RAF with the embedded JPEG after the header.
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+        jpeg.extend(((2 + 6 + tiff.len()) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(&tiff);
+        jpeg.extend([0xff, 0xd9]);
+        let mut raf = b"FUJIFILMCCD-RAW 0201FF383501".to_vec();
+        raf.resize(84, 0);
+        raf.extend(100u32.to_be_bytes());
+        raf.extend((jpeg.len() as u32).to_be_bytes());
+        raf.resize(100, 0);
+        raf.extend(&jpeg);
+        assert_eq!(read("a.raf", &raf), expected);
+
+        // CR3 with IFD0 in `CMT1` and Exif IFD in `CMT2`.
+        let cmt1 = make_box(b"CMT1", &make_tiff(false, &ifd0, &[], &[]));
+        let cmt2 = make_box(b"CMT2", &make_tiff(false, &exif, &[], &[]));
+        let uuid = make_box(b"uuid", &[CR3_UUID.to_vec(), cmt1, cmt2].concat());
+        let cr3 = [
+            make_box(b"ftyp", b"crx \0\0\0\x01crx isom"),
+            make_box(b"moov", &uuid),
+        ]
+        .concat();
+        assert_eq!(read("a.cr3", &cr3), expected);
+    }
+}
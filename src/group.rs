@@ -0,0 +1,118 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::{metadata::Photo, timestamp::Timestamp};
+
+/// One logical photo, numbered as one. e.g. `IMG_1234.CR3` and `IMG_1234.JPG`.
+pub struct Item {
+    pub photos: Vec<Photo>,
+    /// Sidecar files renamed together with the photos.
+    pub sidecars: Vec<PathBuf>,
+}
+
+impl Item {
+    fn new(photo: Photo) -> Self {
+        Self {
+            photos: vec![photo],
+            sidecars: Vec::new(),
+        }
+    }
+
+    /// The earliest capture time of the members.
+    pub fn timestamp(&self) -> Option<&Timestamp> {
+        self.photos
+            .iter()
+            .filter_map(|photo| photo.timestamp.as_ref())
+            .min_by(|t1, t2| t1.time.cmp(&t2.time))
+    }
+}
+
+/// Groups files that share a directory and a stem of the original name.
+/// If `enabled` is false, every file becomes an item on its own.
+pub fn group_by_stem(
+    photos: Vec<Photo>,
+    originals: &HashMap<PathBuf, String>,
+    enabled: bool,
+) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut index: HashMap<(PathBuf, String), usize> = HashMap::new();
+
+    for photo in photos {
+        if !enabled {
+            items.push(Item::new(photo));
+            continue;
+        }
+
+        let key = (
+            photo.path.parent().map(PathBuf::from).unwrap_or_default(),
+            stem(&originals[&photo.path]),
+        );
+        match index.get(&key) {
+            Some(&i) => items[i].photos.push(photo),
+            None => {
+                index.insert(key, items.len());
+                items.push(Item::new(photo));
+            }
+        }
+    }
+
+    items
+}
+
+/// Splits files by their directories.
+pub fn group_by_dir<T: AsRef<Path>>(files: Vec<T>) -> BTreeMap<PathBuf, Vec<T>> {
+    let mut groups: BTreeMap<PathBuf, Vec<T>> = BTreeMap::new();
+    for file in files {
+        let parent = file
+            .as_ref()
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        groups.entry(parent).or_default().push(file);
+    }
+    groups
+}
+
+fn stem(name: &str) -> String {
+    Path::new(name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_group_by_stem() {
+        let files = [
+            "a/IMG_1.CR3",
+            "a/IMG_2.JPG",
+            "a/1__IMG_1.jpg",
+            "b/IMG_1.JPG",
+        ];
+        let originals = files
+            .iter()
+            .map(|f| {
+                let name = Path::new(f).file_name().unwrap().to_string_lossy();
+                (PathBuf::from(f), name.trim_start_matches("1__").to_string())
+            })
+            .collect();
+        let photos = files
+            .iter()
+            .map(|f| Photo {
+                path: PathBuf::from(f),
+                ..Default::default()
+            })
+            .collect();
+
+        let actual: Vec<usize> = group_by_stem(photos, &originals, true)
+            .iter()
+            .map(|item| item.photos.len())
+            .collect();
+        assert_eq!(actual, vec![2, 1, 1]);
+    }
+}
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    plan::{Action, Kind, SortPlan},
+};
+
+/// Directory of files kept by this tool in each sorted directory.
+pub const DATA_DIR: &str = ".photo-sorter";
+const JOURNAL_FILE: &str = "journal.json";
+
+/// Record of renames done in one directory, oldest first.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Journal {
+    pub entries: Vec<Entry>,
+}
+
+/// One rename or copy. Both paths are relative to the directory of the journal,
+/// or absolute if they are outside of it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Whether `to` is a copy, and `from` is left as is.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub copy: bool,
+}
+
+/// Journals of a directory tree, loaded on demand.
+pub struct Journals {
+    root: PathBuf,
+    loaded: HashMap<PathBuf, Option<Resolved>>,
+}
+
+/// Resolved entries of a journal.
+struct Resolved {
+    entries: Vec<Entry>,
+    /// Index of the entry by the current path of the file.
+    index: HashMap<PathBuf, usize>,
+}
+
+impl Journal {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(DATA_DIR).join(JOURNAL_FILE)
+    }
+
+    /// Loads the journal of `dir`. Returns `None` if there is no journal.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path).map_err(Error::io("read journal", &path))?;
+        let journal = serde_json::from_str(&text).map_err(|error| Error::Broken {
+            kind: "journal",
+            path: path.clone(),
+            error,
+        })?;
+        Ok(Some(journal))
+    }
+
+    /// Saves the journal into `dir`. Empty journal is removed instead.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = Self::path(dir);
+        if self.entries.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(Error::io("remove journal", &path))?;
+            }
+            // Leaves the directory if something else is in there.
+            let _ = fs::remove_dir(dir.join(DATA_DIR));
+            return Ok(());
+        }
+
+        let data_dir = dir.join(DATA_DIR);
+        fs::create_dir_all(&data_dir).map_err(Error::io("create journal directory", &data_dir))?;
+        let text = serde_json::to_string_pretty(self).map_err(|error| Error::Broken {
+            kind: "journal",
+            path: path.clone(),
+            error,
+        })?;
+        fs::write(&path, text).map_err(Error::io("write journal", &path))?;
+        Ok(())
+    }
+
+    pub fn push<P: Into<PathBuf>, Q: Into<PathBuf>>(&mut self, from: P, to: Q) {
+        self.entries.push(Entry {
+            from: from.into(),
+            to: to.into(),
+            copy: false,
+        });
+    }
+
+    pub fn push_copy<P: Into<PathBuf>, Q: Into<PathBuf>>(&mut self, from: P, to: Q) {
+        self.entries.push(Entry {
+            from: from.into(),
+            to: to.into(),
+            copy: true,
+        });
+    }
+
+    /// Resolves chained renames into pairs of current name and original name.
+    /// A copy is never chained to an earlier entry, because its source stays.
+    pub fn originals(&self) -> Vec<Entry> {
+        let mut resolved: Vec<Entry> = Vec::new();
+        for entry in self.entries.iter() {
+            let earlier = match entry.copy {
+                true => None,
+                false => resolved.iter().position(|r| r.to == entry.from),
+            };
+            let (from, copy) = match earlier {
+                Some(i) => {
+                    let earlier = resolved.remove(i);
+                    (earlier.from, earlier.copy)
+                }
+                None => (entry.from.clone(), entry.copy),
+            };
+            if from == entry.to {
+                continue;
+            }
+            resolved.push(Entry {
+                from,
+                to: entry.to.clone(),
+                copy,
+            });
+        }
+        resolved
+    }
+}
+
+impl Journals {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: PathBuf::from(root),
+            loaded: HashMap::new(),
+        }
+    }
+
+    /// Resolved entries of the journal of `dir`. Returns `None` if there is no journal.
+    pub fn entries(&mut self, dir: &Path) -> Result<Option<&[Entry]>> {
+        Ok(self.load(dir)?.map(|resolved| resolved.entries.as_slice()))
+    }
+
+    /// Finds the journal that knows the file, in the directory of the file and
+    /// its ancestors up to the root, nearest first.
+    /// Returns the directory of the journal and the entry of the file.
+    pub fn find(&mut self, file: &Path) -> Result<Option<(PathBuf, Entry)>> {
+        let root = self.root.clone();
+        for dir in file.ancestors().skip(1) {
+            if !dir.starts_with(&root) {
+                break;
+            }
+            if let Some(resolved) = self.load(dir)? {
+                if let Some(&i) = resolved.index.get(file) {
+                    return Ok(Some((PathBuf::from(dir), resolved.entries[i].clone())));
+                }
+            }
+            if dir == root {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    fn load(&mut self, dir: &Path) -> Result<Option<&Resolved>> {
+        if !self.loaded.contains_key(dir) {
+            let resolved = Journal::load(dir)?.map(|journal| {
+                let entries = journal.originals();
+                let index = entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| (dir.join(&entry.to), i))
+                    .collect();
+                Resolved { entries, index }
+            });
+            self.loaded.insert(PathBuf::from(dir), resolved);
+        }
+        Ok(self.loaded[dir].as_ref())
+    }
+}
+
+/// Path of `path` as written in the journal of `dir`.
+pub fn relative_path(dir: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(dir) {
+        Ok(relative) => PathBuf::from(relative),
+        Err(_) => std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path)),
+    }
+}
+
+/// Records the applied plan into the journal of each directory.
+pub fn record(plan: &SortPlan) -> Result<()> {
+    let mut journals: BTreeMap<PathBuf, Journal> = BTreeMap::new();
+    // Links to duplicates keep the same content, and are not undone.
+    let ops = plan
+        .ops
+        .iter()
+        .filter(|op| op.action != Action::ReplaceWithLink);
+    for op in ops {
+        let dir = op.base();
+        if !journals.contains_key(dir) {
+            let journal = Journal::load(dir)?;
+            // Directories reverted without a journal need no record.
+            if journal.is_none() && plan.kind == Kind::Revert {
+                continue;
+            }
+            journals.insert(PathBuf::from(dir), journal.unwrap_or_default());
+        }
+        let journal = journals.get_mut(dir).unwrap();
+        let (from, to) = (relative_path(dir, &op.from), relative_path(dir, &op.to));
+        match op.action {
+            Action::Copy | Action::Link => journal.push_copy(from, to),
+            _ => journal.push(from, to),
+        }
+    }
+
+    for (dir, journal) in journals.iter_mut() {
+        journal.entries = journal.originals();
+        journal.save(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_originals() {
+        let mut journal = Journal::default();
+        journal.push("a__b.jpg", "1__a__b.jpg");
+        journal.push("c.jpg", "2__c.jpg");
+        journal.push("1__a__b.jpg", "2__a__b.jpg");
+        journal.push("2__c.jpg", "c.jpg");
+
+        let actual: Vec<_> = journal
+            .originals()
+            .into_iter()
+            .map(|e| (e.to, e.from))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![(PathBuf::from("2__a__b.jpg"), PathBuf::from("a__b.jpg"))]
+        );
+
+        // A copy keeps its source, and later renames of the copy are chained to it.
+        let mut journal = Journal::default();
+        journal.push("a.jpg", "1__a.jpg");
+        journal.push_copy("1__a.jpg", "2024/1__a.jpg");
+        journal.push("2024/1__a.jpg", "2024/2__a.jpg");
+        let actual: Vec<_> = journal
+            .originals()
+            .into_iter()
+            .map(|e| (e.to, e.copy))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (PathBuf::from("1__a.jpg"), false),
+                (PathBuf::from("2024/2__a.jpg"), true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Sorts photos and videos by capture time, and renames them with sequence numbers.
+//!
+//! [`scan`] lists files, [`plan`] or [`plan_revert`] makes a [`SortPlan`],
+//! and [`apply`] renames the files and records them in the journals.
+
+pub mod burst;
+mod checksum;
+pub mod config;
+pub mod duplicate;
+mod error;
+pub mod event;
+pub mod filename;
+pub mod formats;
+pub mod group;
+mod journal;
+mod live;
+pub mod metadata;
+pub mod plan;
+mod plan_file;
+mod revert;
+mod scan;
+mod sidecar;
+pub mod similar;
+mod sort;
+pub mod source;
+pub mod template;
+pub mod timestamp;
+mod video;
+
+pub use error::{Error, Result};
+pub use metadata::Photo;
+pub use plan::{RenameOp, SortPlan};
+pub use revert::plan_revert;
+pub use scan::{scan, ScanOptions};
+pub use sort::{plan, Similarity, SortOptions, Transfer};
+
+/// Applies the plan, and records it in the journals so that it can be reverted.
+///
+/// Returns `Error::Incomplete` if the files are renamed and recorded,
+/// but some copies are not removed or some duplicates are not replaced with links.
+pub fn apply(plan: &SortPlan) -> Result<()> {
+    let failures = plan.apply()?;
+    journal::record(plan)?;
+    if !failures.is_empty() {
+        return Err(Error::Incomplete(failures));
+    }
+    Ok(())
+}
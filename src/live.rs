@@ -0,0 +1,215 @@
+use exif::{Exif, In, Tag, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    group::Item,
+    video::{meta_item, read_moov},
+};
+
+/// Key of the Live Photo identifier in `meta` of iPhone videos.
+const APPLE_CONTENT_ID: &[u8] = b"com.apple.quicktime.content.identifier";
+/// Tag of the Live Photo identifier in Apple MakerNote.
+const MAKER_NOTE_CONTENT_ID: u16 = 0x0011;
+/// Leading bytes of JPEG searched for the Motion Photo marks in XMP.
+const MOTION_SEARCH_SIZE: u64 = 256 * 1024;
+/// XMP marks of Android Motion Photos (`PXL_*.MP.jpg`) and older Micro Videos (`MVIMG_*.jpg`).
+const MOTION_MARKS: &[&[u8]] = &[
+    b"MotionPhoto=\"1\"",
+    b"MicroVideo=\"1\"",
+    b"<GCamera:MotionPhoto>1<",
+    b"<GCamera:MicroVideo>1<",
+];
+
+/// Reads the Live Photo identifier from Apple MakerNote of a still image.
+pub fn image_content_id(exif: &[Exif]) -> Option<String> {
+    let field = exif
+        .iter()
+        .find_map(|exif| exif.get_field(Tag::MakerNote, In::PRIMARY))?;
+    let Value::Undefined(note, _) = &field.value else {
+        return None;
+    };
+    apple_maker_note_ascii(note, MAKER_NOTE_CONTENT_ID)
+}
+
+/// Reads the Live Photo identifier from QuickTime metadata of a video.
+pub fn video_content_id(path: &Path) -> Option<String> {
+    let moov = read_moov(&mut fs::File::open(path).ok()?)?;
+    let value = meta_item(&moov, APPLE_CONTENT_ID)?;
+    let id = String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string();
+    (!id.is_empty()).then_some(id)
+}
+
+/// Checks whether the JPEG is an Android Motion Photo with an embedded video.
+pub fn is_motion_photo(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::new();
+    if file
+        .take(MOTION_SEARCH_SIZE)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return false;
+    }
+    MOTION_MARKS
+        .iter()
+        .any(|mark| head.windows(mark.len()).any(|w| w == *mark))
+}
+
+/// Reads an ASCII value from Apple MakerNote.
+///
+/// The note starts with `Apple iOS\0`, a version and a byte order mark,
+/// followed by an IFD whose offsets are relative to the start of the note.
+pub fn apple_maker_note_ascii(note: &[u8], tag: u16) -> Option<String> {
+    if !note.starts_with(b"Apple iOS\0") {
+        return None;
+    }
+    let big_endian = match note.get(12..14)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| -> Option<u16> {
+        let bytes = note.get(pos..pos + 2)?.try_into().unwrap();
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |pos: usize| -> Option<u32> {
+        let bytes = note.get(pos..pos + 4)?.try_into().unwrap();
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    let count = u16_at(14)? as usize;
+    for i in 0..count {
+        let entry = 16 + i * 12;
+        // Type 2 is ASCII.
+        if u16_at(entry)? != tag || u16_at(entry + 2)? != 2 {
+            continue;
+        }
+        let len = u32_at(entry + 4)? as usize;
+        let value = if len <= 4 {
+            note.get(entry + 8..entry + 8 + len)?
+        } else {
+            let offset = u32_at(entry + 8)? as usize;
+            note.get(offset..offset + len)?
+        };
+        let value = String::from_utf8_lossy(value)
+            .trim_end_matches('\0')
+            .to_string();
+        return (!value.is_empty()).then_some(value);
+    }
+    None
+}
+
+/// Merges items that share a Live Photo identifier in the same directory,
+/// so that a still image and its video are numbered as one.
+pub fn pair_live_photos(items: Vec<Item>) -> Vec<Item> {
+    let mut merged: Vec<Item> = Vec::new();
+    let mut index: HashMap<(PathBuf, String), usize> = HashMap::new();
+
+    for item in items {
+        let key = item.photos.iter().find_map(|photo| {
+            let id = photo.content_id.clone()?;
+            Some((
+                photo.path.parent().map(PathBuf::from).unwrap_or_default(),
+                id,
+            ))
+        });
+        match key.as_ref().and_then(|key| index.get(key)) {
+            Some(&i) => {
+                merged[i].photos.extend(item.photos);
+                merged[i].sidecars.extend(item.sidecars);
+            }
+            None => {
+                if let Some(key) = key {
+                    index.insert(key, merged.len());
+                }
+                merged.push(item);
+            }
+        }
+    }
+
+    // Puts still images before videos.
+    for item in merged.iter_mut() {
+        item.photos.sort_by_key(|photo| photo.is_video());
+    }
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metadata::Photo;
+
+    #[test]
+    fn test_apple_maker_note_ascii() {
+        let id = b"ABCDEF01-2345-6789-ABCD-EF0123456789\0";
+        let mut note = b"Apple iOS\0\0\x01MM".to_vec();
+        note.extend_from_slice(&1u16.to_be_bytes());
+        note.extend_from_slice(&MAKER_NOTE_CONTENT_ID.to_be_bytes());
+        note.extend_from_slice(&2u16.to_be_bytes());
+        note.extend_from_slice(&(id.len() as u32).to_be_bytes());
+        note.extend_from_slice(&32u32.to_be_bytes());
+        note.extend_from_slice(&[0; 4]);
+        note.extend_from_slice(id);
+
+        let actual = apple_maker_note_ascii(&note, MAKER_NOTE_CONTENT_ID);
+        assert_eq!(
+            actual.as_deref(),
+            Some("ABCDEF01-2345-6789-ABCD-EF0123456789")
+        );
+        assert_eq!(apple_maker_note_ascii(&note, 0x0008), None);
+    }
+
+    #[test]
+    fn test_pair_live_photos() {
+        let item = |path: &str, id: Option<&str>| Item {
+            photos: vec![Photo {
+                path: PathBuf::from(path),
+                content_id: id.map(String::from),
+                ..Default::default()
+            }],
+            sidecars: Vec::new(),
+        };
+        let paths = |item: &Item| -> Vec<PathBuf> {
+            item.photos.iter().map(|photo| photo.path.clone()).collect()
+        };
+
+        let items = pair_live_photos(vec![
+            item("a/IMG_E0001.MOV", Some("ID1")),
+            item("a/IMG_0002.HEIC", None),
+            Item {
+                sidecars: vec![PathBuf::from("a/IMG_0001.AAE")],
+                ..item("a/IMG_0001.HEIC", Some("ID1"))
+            },
+            item("b/IMG_0003.HEIC", Some("ID1")),
+        ]);
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            paths(&items[0]),
+            vec![
+                PathBuf::from("a/IMG_0001.HEIC"),
+                PathBuf::from("a/IMG_E0001.MOV")
+            ]
+        );
+        assert_eq!(items[0].sidecars, vec![PathBuf::from("a/IMG_0001.AAE")]);
+        assert_eq!(paths(&items[1]), vec![PathBuf::from("a/IMG_0002.HEIC")]);
+        // Not paired across directories.
+        assert_eq!(paths(&items[2]), vec![PathBuf::from("b/IMG_0003.HEIC")]);
+    }
+}
@@ -0,0 +1,231 @@
+use exif::{Exif, Tag};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    burst::Shot,
+    formats::{exif_ascii, VIDEO_EXTENSIONS},
+    live::{image_content_id, is_motion_photo, video_content_id},
+    source::{SourceFile, TimeReader},
+    timestamp::Timestamp,
+};
+
+/// Called with the label of the step, the number of files done and the total,
+/// each time a file is done. Calls are one at a time, in the order of the number.
+pub type Progress<'a> = dyn Fn(&str, usize, usize) + Sync + 'a;
+
+/// A file with its metadata, read once before sorting.
+#[derive(Default)]
+pub struct Photo {
+    pub path: PathBuf,
+    pub timestamp: Option<Timestamp>,
+    /// Identifier shared by the still image and the video of a Live Photo.
+    pub content_id: Option<String>,
+    /// Whether the file is an Android Motion Photo with an embedded video.
+    pub motion: bool,
+    pub camera: Camera,
+    pub shot: Shot,
+}
+
+/// Camera that took the photo, from Exif.
+#[derive(Clone, Default)]
+pub struct Camera {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub lens: Option<String>,
+}
+
+/// Metadata read besides the capture time. Exif is not read for what is not needed.
+#[derive(Clone, Copy)]
+pub struct Extras {
+    /// Identifiers of Live Photos and Motion Photos.
+    pub live: bool,
+    pub camera: bool,
+    /// Bursts and exposure brackets.
+    pub shot: bool,
+}
+
+impl Camera {
+    fn from_exif(exif: &[Exif]) -> Self {
+        let text = |tag| exif_ascii(exif, tag).filter(|text| !text.is_empty());
+        Self {
+            make: text(Tag::Make),
+            model: text(Tag::Model),
+            serial: text(Tag::BodySerialNumber),
+            lens: text(Tag::LensModel),
+        }
+    }
+
+    /// `Make Model`, or `Model` if it already starts with the make.
+    pub fn name(&self) -> Option<String> {
+        match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => {
+                Some(model.clone())
+            }
+            (Some(make), Some(model)) => Some(format!("{make} {model}")),
+            (None, Some(model)) => Some(model.clone()),
+            _ => None,
+        }
+    }
+
+    /// Checks whether `name` is `Make Model` or `Model` of the camera, ignoring case.
+    pub fn is(&self, name: &str) -> bool {
+        [self.name(), self.model.clone()]
+            .iter()
+            .flatten()
+            .any(|n| n.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl Photo {
+    /// Extension of the file in lower case.
+    pub fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default()
+    }
+
+    pub fn is_video(&self) -> bool {
+        VIDEO_EXTENSIONS.contains(&self.extension().as_str())
+    }
+}
+
+impl AsRef<Path> for Photo {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Reads metadata of all files with `jobs` threads. The order of files is kept.
+pub fn read_photos(
+    files: Vec<PathBuf>,
+    reader: &TimeReader,
+    extras: Extras,
+    jobs: usize,
+    progress: Option<&Progress>,
+) -> Vec<Photo> {
+    map_files(&files, jobs, "Reading metadata", progress, |file| {
+        read_photo(file, reader, extras)
+    })
+}
+
+/// Runs `f` on all files with `jobs` threads, reporting the progress with the label.
+/// The order of files is kept.
+pub fn map_files<T, F>(
+    files: &[PathBuf],
+    jobs: usize,
+    label: &str,
+    progress: Option<&Progress>,
+    f: F,
+) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    let total = files.len();
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..total).map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, total.max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(index) else {
+                    break;
+                };
+                let result = f(file);
+                let mut results = results.lock().unwrap();
+                results[index] = Some(result);
+                // Reported in the lock, so that the numbers are in order.
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(progress) = progress {
+                    progress(label, done, total);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Reads metadata of one file.
+pub fn read_photo(path: &Path, reader: &TimeReader, extras: Extras) -> Photo {
+    let mut photo = Photo {
+        path: PathBuf::from(path),
+        ..Default::default()
+    };
+    let file = SourceFile::new(path);
+    photo.timestamp = reader.read(&file);
+    if photo.is_video() {
+        if extras.live {
+            photo.content_id = video_content_id(path);
+        }
+        return photo;
+    }
+
+    if extras.live {
+        photo.content_id = image_content_id(file.exif());
+        photo.motion =
+            ["jpg", "jpeg"].contains(&photo.extension().as_str()) && is_motion_photo(path);
+    }
+    if extras.camera {
+        photo.camera = Camera::from_exif(file.exif());
+    }
+    if extras.shot {
+        photo.shot = Shot::from_exif(file.exif());
+    }
+    photo
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        filename::FilenamePattern,
+        source::{TimeReader, TimeSource},
+    };
+
+    #[test]
+    fn test_read_photos_order() {
+        // Times from the names, latest first, so that the order is not sorted by chance.
+        let files: Vec<PathBuf> = (0..500)
+            .rev()
+            .map(|n| PathBuf::from(format!("IMG_20240501_{:02}{:02}00.jpg", n / 60, n % 60)))
+            .collect();
+        let reader = TimeReader::new(&[TimeSource::Filename], &FilenamePattern::builtin());
+        let extras = Extras {
+            live: false,
+            camera: false,
+            shot: false,
+        };
+
+        let reported = Mutex::new(Vec::new());
+        let progress = |_: &str, done: usize, _: usize| reported.lock().unwrap().push(done);
+        let photos = read_photos(files.clone(), &reader, extras, 8, Some(&progress));
+        assert!(reported
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .eq(1..=files.len()));
+        assert_eq!(photos.len(), files.len());
+        for (photo, file) in photos.iter().zip(files.iter()) {
+            assert_eq!(&photo.path, file);
+            let time = photo.timestamp.unwrap().time.local.format("%H%M%S");
+            assert!(file.to_string_lossy().contains(&time.to_string()));
+        }
+    }
+}
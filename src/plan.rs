@@ -0,0 +1,479 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checksum::copy_verified,
+    error::{Error, Result},
+    timestamp::Timestamp,
+};
+
+/// What a plan does to the files. Only changes messages.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    Rename,
+    Revert,
+}
+
+/// One file to be renamed.
+#[derive(Clone)]
+pub struct RenameOp {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Capture time the file was sorted by.
+    pub timestamp: Option<Timestamp>,
+    /// Role of the file in its photo.
+    pub role: Role,
+    /// Whether the file is an Android Motion Photo.
+    pub motion: bool,
+    pub action: Action,
+    /// Directory whose journal records the op. The directory of `from` if `None`.
+    pub base: Option<PathBuf>,
+}
+
+/// What an op does to the file.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Moves `from` to `to`.
+    Rename,
+    /// Copies `from` to `to`, leaving `from` as is. The copy is verified by checksum.
+    Copy,
+    /// Makes `to` a hard link to `from`.
+    Link,
+    /// Removes `from`, which is a copy of `to`.
+    RemoveCopy,
+    /// Replaces `from`, which has the same content as `to`, with a hard link to `to`.
+    ReplaceWithLink,
+}
+
+/// Role of a file in one logical photo, which is numbered as one.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// The first file of the photo.
+    Main,
+    /// Another file of the photo, e.g. JPEG of RAW+JPEG.
+    Pair,
+    /// Video of a Live Photo.
+    LiveVideo,
+    /// Sidecar file of the photo.
+    Sidecar,
+    /// File with the same content as another file.
+    Duplicate,
+}
+
+/// Full set of renames that are applied as one batch.
+pub struct SortPlan {
+    pub kind: Kind,
+    pub ops: Vec<RenameOp>,
+    /// Messages about files found while planning, e.g. duplicates and files left as is.
+    pub notes: Vec<String>,
+}
+
+impl RenameOp {
+    pub fn new<P: Into<PathBuf>, Q: Into<PathBuf>>(from: P, to: Q) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            timestamp: None,
+            role: Role::Main,
+            motion: false,
+            action: Action::Rename,
+            base: None,
+        }
+    }
+
+    fn is_noop(&self) -> bool {
+        self.action == Action::Rename && self.from == self.to
+    }
+
+    /// Whether `from` is moved away by the op.
+    fn moves_source(&self) -> bool {
+        matches!(self.action, Action::Rename | Action::RemoveCopy)
+    }
+
+    /// Verb of the action in messages.
+    fn verb(&self) -> &'static str {
+        match self.action {
+            Action::Rename => "rename",
+            Action::Copy => "copy",
+            Action::Link => "link",
+            Action::RemoveCopy => "remove",
+            Action::ReplaceWithLink => "link",
+        }
+    }
+
+    /// Directory whose journal records the op.
+    pub fn base(&self) -> &Path {
+        match &self.base {
+            Some(base) => base,
+            None => self.from.parent().unwrap_or(Path::new("")),
+        }
+    }
+
+    /// Path shown to users, relative to the base, or to the directory of `from`.
+    pub fn show(&self, path: &Path) -> String {
+        let base = match &self.base {
+            Some(base) => base.as_path(),
+            None => self.from.parent().unwrap_or(Path::new("")),
+        };
+        match path.strip_prefix(base) {
+            Ok(relative) => relative.to_string_lossy().to_string(),
+            Err(_) if self.base.is_some() => path.to_string_lossy().to_string(),
+            Err(_) => file_name(path),
+        }
+    }
+}
+
+impl SortPlan {
+    pub fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            ops: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, op: RenameOp) {
+        if !op.is_noop() {
+            self.ops.push(op);
+        }
+    }
+
+    /// Checks that no file is overwritten by the plan.
+    ///
+    /// Targets that are sources of other ops are allowed, including cycles,
+    /// because `apply` moves every source aside before renaming.
+    pub fn check(&self) -> Result<()> {
+        let sources: HashSet<&Path> = self
+            .ops
+            .iter()
+            .filter(|op| op.moves_source())
+            .map(|op| op.from.as_path())
+            .collect();
+        let mut targets: HashMap<&Path, &Path> = HashMap::new();
+        let mut errors = Vec::new();
+
+        for op in self.ops.iter() {
+            if matches!(op.action, Action::RemoveCopy | Action::ReplaceWithLink) {
+                continue;
+            }
+            if let Some(other) = targets.insert(&op.to, &op.from) {
+                errors.push(format!(
+                    "{} and {} are both renamed to {}",
+                    other.to_string_lossy(),
+                    op.from.to_string_lossy(),
+                    op.to.to_string_lossy()
+                ));
+            } else if !sources.contains(op.to.as_path()) && fs::symlink_metadata(&op.to).is_ok() {
+                errors.push(format!(
+                    "{} cannot be renamed to {}: file already exists",
+                    op.from.to_string_lossy(),
+                    op.to.to_string_lossy()
+                ));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(Error::Conflicts(errors));
+        }
+        Ok(())
+    }
+
+    /// Renames all files through temporary names, creating directories as needed.
+    /// If any rename fails, the renamed files are moved back.
+    ///
+    /// Returns the failures of removing copies and linking duplicates, which are done last,
+    /// after the files are renamed.
+    pub(crate) fn apply(&self) -> Result<Vec<String>> {
+        self.check()?;
+
+        // Copies are made next to the target, other files are moved aside next to the source.
+        let temps = self
+            .ops
+            .iter()
+            .enumerate()
+            .map(|(index, op)| match op.action {
+                Action::Copy => temp_path(&op.to, index),
+                _ => temp_path(&op.from, index),
+            })
+            .collect::<Vec<_>>();
+
+        // Phase 1: moves every source aside.
+        for (index, op) in self.ops.iter().enumerate() {
+            if !op.moves_source() {
+                continue;
+            }
+            if let Err(e) = fs::rename(&op.from, &temps[index]) {
+                let failures = self.rollback(&temps, index, 0);
+                return Err(Error::rollback(
+                    Error::io("rename file", &op.from)(e),
+                    failures,
+                ));
+            }
+        }
+
+        // Phase 2: moves temporary files to targets, and makes copies.
+        for (index, op) in self.ops.iter().enumerate() {
+            if let Err(e) = place(op, &temps[index]) {
+                let failures = self.rollback(&temps, self.ops.len(), index);
+                return Err(Error::rollback(Error::io(op.verb(), &op.from)(e), failures));
+            }
+        }
+
+        // Phase 3: removes copies put aside, and replaces duplicates with links.
+        // This can not be rolled back.
+        let mut failures = Vec::new();
+        for (index, op) in self.ops.iter().enumerate() {
+            match op.action {
+                Action::RemoveCopy => {
+                    if let Err(e) = fs::remove_file(&temps[index]) {
+                        failures.push(format!(
+                            "Failed to remove {} (left as {}): {e}",
+                            op.from.to_string_lossy(),
+                            temps[index].to_string_lossy()
+                        ));
+                    }
+                }
+                Action::ReplaceWithLink => {
+                    if let Err(e) = replace_with_link(&op.from, self.final_path(&op.to), index) {
+                        failures.push(format!(
+                            "Failed to replace {} with a hard link: {e}",
+                            op.from.to_string_lossy()
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.prune_dirs();
+
+        Ok(failures)
+    }
+
+    /// Undoes the first `moved` ops of phase 1 and the first `placed` ops of phase 2.
+    /// Returns the files that can not be moved back.
+    fn rollback(&self, temps: &[PathBuf], moved: usize, placed: usize) -> Vec<String> {
+        let mut failures = Vec::new();
+        for index in (0..placed).rev() {
+            let op = &self.ops[index];
+            let result = match op.action {
+                Action::Rename => move_file(&op.to, &temps[index]),
+                Action::Copy | Action::Link => fs::remove_file(&op.to),
+                Action::RemoveCopy | Action::ReplaceWithLink => Ok(()),
+            };
+            if result.is_err() {
+                failures.push(format!("Failed to roll back {}", op.to.to_string_lossy()));
+            }
+        }
+        for index in (0..moved).rev() {
+            let op = &self.ops[index];
+            if op.moves_source() && fs::rename(&temps[index], &op.from).is_err() {
+                failures.push(format!(
+                    "Failed to roll back {} (left as {})",
+                    op.from.to_string_lossy(),
+                    temps[index].to_string_lossy()
+                ));
+            }
+        }
+        failures
+    }
+
+    /// Path of the file after the plan is applied.
+    fn final_path<'a>(&'a self, path: &'a Path) -> &'a Path {
+        self.ops
+            .iter()
+            .find(|op| op.moves_source() && op.from == path)
+            .map_or(path, |op| op.to.as_path())
+    }
+
+    /// Removes directories under the base that are emptied by moving files out.
+    fn prune_dirs(&self) {
+        for op in self.ops.iter().filter(|op| op.moves_source()) {
+            let Some(base) = &op.base else {
+                continue;
+            };
+            for dir in op.from.ancestors().skip(1) {
+                if dir == base || !dir.starts_with(base) || fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Puts the file to the target in phase 2.
+fn place(op: &RenameOp, temp: &Path) -> io::Result<()> {
+    if matches!(op.action, Action::RemoveCopy | Action::ReplaceWithLink) {
+        return Ok(());
+    }
+    if let Some(dir) = op.to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    match op.action {
+        Action::Copy => {
+            // Copies to the temporary name, so that a partial copy never has the target name.
+            if let Err(e) = copy_verified(&op.from, temp).and_then(|_| fs::rename(temp, &op.to)) {
+                let _ = fs::remove_file(temp);
+                return Err(e);
+            }
+            Ok(())
+        }
+        Action::Link => fs::hard_link(&op.from, &op.to),
+        _ => move_file(temp, &op.to),
+    }
+}
+
+/// Renames the file, or copies and removes it if the target is on another file system.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_verified(from, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Makes a hard link under a temporary name and renames it over the file,
+/// so that the file is never lost.
+fn replace_with_link(file: &Path, original: &Path, index: usize) -> io::Result<()> {
+    let temp = temp_path(file, index);
+    fs::hard_link(original, &temp)?;
+    if let Err(e) = fs::rename(&temp, file) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn temp_path(file: &Path, index: usize) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    let pid = std::process::id();
+    let mut n = 0;
+    loop {
+        let temp = parent.join(format!(".photo-sorter-{pid}-{index}-{n}.tmp"));
+        if fs::symlink_metadata(&temp).is_err() {
+            return temp;
+        }
+        n += 1;
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Makes a temporary directory with the files, whose contents are their names.
+    fn setup(files: &[&str]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            fs::write(dir.path().join(file), file).unwrap();
+        }
+        dir
+    }
+
+    fn rename_plan(dir: &Path, ops: &[(&str, &str)]) -> SortPlan {
+        let mut plan = SortPlan::new(Kind::Rename);
+        for (from, to) in ops {
+            plan.push(RenameOp::new(dir.join(from), dir.join(to)));
+        }
+        plan
+    }
+
+    fn read(dir: &Path, file: &str) -> String {
+        fs::read_to_string(dir.join(file)).unwrap()
+    }
+
+    /// Names in the directory, without the temporary files.
+    fn list(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_check_conflicts() {
+        let dir = setup(&["a.jpg", "b.jpg", "c.jpg"]);
+        let dir = dir.path();
+
+        let plan = rename_plan(dir, &[("a.jpg", "c.jpg")]);
+        assert!(matches!(plan.check(), Err(Error::Conflicts(_))));
+        assert!(matches!(plan.apply(), Err(Error::Conflicts(_))));
+
+        let plan = rename_plan(dir, &[("a.jpg", "1.jpg"), ("b.jpg", "1.jpg")]);
+        assert!(matches!(plan.check(), Err(Error::Conflicts(_))));
+        assert!(plan.apply().is_err());
+
+        // Nothing is touched by refused plans.
+        assert_eq!(list(dir), vec!["a.jpg", "b.jpg", "c.jpg"]);
+    }
+
+    #[test]
+    fn test_apply_cycle() {
+        let dir = setup(&["a.jpg", "b.jpg", "c.jpg"]);
+        let dir = dir.path();
+
+        let plan = rename_plan(dir, &[("a.jpg", "b.jpg"), ("b.jpg", "a.jpg")]);
+        plan.apply().unwrap();
+        assert_eq!(
+            (read(dir, "a.jpg"), read(dir, "b.jpg")),
+            ("b.jpg".into(), "a.jpg".into())
+        );
+
+        let plan = rename_plan(
+            dir,
+            &[("a.jpg", "b.jpg"), ("b.jpg", "c.jpg"), ("c.jpg", "a.jpg")],
+        );
+        plan.apply().unwrap();
+        assert_eq!(read(dir, "a.jpg"), "c.jpg");
+        assert_eq!(read(dir, "b.jpg"), "b.jpg");
+        assert_eq!(read(dir, "c.jpg"), "a.jpg");
+        assert_eq!(list(dir), vec!["a.jpg", "b.jpg", "c.jpg"]);
+    }
+
+    #[test]
+    fn test_rollback() {
+        // `blocker` is a file, so that its folder can not be made in phase 2.
+        let dir = setup(&["a.jpg", "b.jpg", "blocker"]);
+        let dir = dir.path();
+
+        let plan = rename_plan(dir, &[("a.jpg", "b.jpg"), ("b.jpg", "blocker/b.jpg")]);
+        assert!(matches!(plan.apply(), Err(Error::Io { .. })));
+        assert_eq!(read(dir, "a.jpg"), "a.jpg");
+        assert_eq!(read(dir, "b.jpg"), "b.jpg");
+        assert_eq!(list(dir), vec!["a.jpg", "b.jpg", "blocker"]);
+    }
+
+    #[test]
+    fn test_incomplete() {
+        let dir = setup(&["a.jpg"]);
+        let dir = dir.path();
+
+        // The original to link to is missing, which is found only in phase 3.
+        let mut plan = rename_plan(dir, &[("a.jpg", "missing.jpg")]);
+        plan.ops[0].action = Action::ReplaceWithLink;
+        match crate::apply(&plan) {
+            Err(Error::Incomplete(failures)) => assert_eq!(failures.len(), 1),
+            _ => panic!("phase 3 failure is not returned"),
+        }
+        assert_eq!(list(dir), vec!["a.jpg"]);
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{self, Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    plan::{Action, Kind, RenameOp, Role, SortPlan},
+};
+
+/// Plan saved to be reviewed, edited by hand, and applied later.
+#[derive(Serialize, Deserialize)]
+struct PlanFile {
+    kind: Kind,
+    ops: Vec<SavedOp>,
+}
+
+/// One op with the state of `from` when the plan was saved. Paths are absolute.
+#[derive(Serialize, Deserialize)]
+struct SavedOp {
+    from: PathBuf,
+    to: PathBuf,
+    /// Capture time the file was sorted by, only for review.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    /// Where the capture time was taken from, only for review.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    action: Action,
+    role: Role,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    motion: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base: Option<PathBuf>,
+    /// Size of `from` in bytes.
+    size: u64,
+    /// Modified time of `from` in RFC 3339.
+    modified: String,
+}
+
+impl SortPlan {
+    /// Saves the plan as JSON, with the size and the modified time of each file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let ops = self
+            .ops
+            .iter()
+            .map(|op| {
+                let (size, modified) = stat(&op.from)?;
+                Ok(SavedOp {
+                    from: absolute(&op.from)?,
+                    to: absolute(&op.to)?,
+                    timestamp: op.timestamp.map(|timestamp| timestamp.time.to_string()),
+                    source: op.timestamp.map(|timestamp| timestamp.source.to_string()),
+                    action: op.action,
+                    role: op.role,
+                    motion: op.motion,
+                    base: op.base.as_deref().map(absolute).transpose()?,
+                    size,
+                    modified,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let file = PlanFile {
+            kind: self.kind,
+            ops,
+        };
+
+        let text = serde_json::to_string_pretty(&file).map_err(|error| Error::Broken {
+            kind: "plan",
+            path: PathBuf::from(path),
+            error,
+        })?;
+        fs::write(path, text).map_err(Error::io("write plan", path))
+    }
+
+    /// Loads a saved plan. Refuses it if any file is gone or changed since it was saved.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(Error::io("read plan", path))?;
+        let file: PlanFile = serde_json::from_str(&text).map_err(|error| Error::Broken {
+            kind: "plan",
+            path: PathBuf::from(path),
+            error,
+        })?;
+
+        let mut stale = Vec::new();
+        for op in file.ops.iter() {
+            match stat(&op.from) {
+                Ok((size, modified)) if size == op.size && modified == op.modified => {}
+                Ok(_) => stale.push(format!("{} is changed", op.from.to_string_lossy())),
+                Err(_) => stale.push(format!("{} is not found", op.from.to_string_lossy())),
+            }
+        }
+        if !stale.is_empty() {
+            return Err(Error::Stale(stale));
+        }
+
+        let mut plan = Self::new(file.kind);
+        for op in file.ops {
+            plan.push(RenameOp {
+                role: op.role,
+                motion: op.motion,
+                action: op.action,
+                base: op.base,
+                ..RenameOp::new(op.from, op.to)
+            });
+        }
+        Ok(plan)
+    }
+}
+
+fn stat(path: &Path) -> Result<(u64, String)> {
+    let metadata = fs::metadata(path).map_err(Error::io("read", path))?;
+    let modified = metadata.modified().map_err(Error::io("read", path))?;
+    let modified = DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Nanos, true);
+    Ok((metadata.len(), modified))
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    path::absolute(path).map_err(Error::io("resolve", path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let (photo, path) = (dir.join("a.jpg"), dir.join("plan.json"));
+        fs::write(&photo, "photo").unwrap();
+
+        let mut plan = SortPlan::new(Kind::Rename);
+        plan.push(RenameOp::new(&photo, dir.join("1__a.jpg")));
+        plan.save(&path).unwrap();
+        let loaded = SortPlan::load(&path).unwrap();
+        assert_eq!(loaded.ops.len(), 1);
+        assert_eq!(loaded.ops[0].to, dir.join("1__a.jpg"));
+
+        fs::write(&photo, "edited").unwrap();
+        assert!(matches!(SortPlan::load(&path), Err(Error::Stale(_))));
+    }
+}
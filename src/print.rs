@@ -0,0 +1,88 @@
+use std::io::{self, IsTerminal, Write};
+
+use photo_sorter::{
+    plan::{Action, Kind, Role},
+    SortPlan,
+};
+
+/// Number of files from which the progress is shown.
+const PROGRESS_THRESHOLD: usize = 200;
+
+/// Shows the plan without renaming.
+/// Files of one photo are listed under its main file.
+pub fn print_plan(plan: &SortPlan) {
+    for op in plan.ops.iter() {
+        let (from, to) = (op.show(&op.from), op.show(&op.to));
+        match op.action {
+            Action::RemoveCopy => {
+                println!("{from} is removed  (copy of {to})");
+                continue;
+            }
+            Action::ReplaceWithLink => {
+                println!("{from} is replaced by a hard link to {to}  (duplicate)");
+                continue;
+            }
+            _ => {}
+        }
+        if plan.kind == Kind::Revert {
+            println!("{from} -> {to}");
+            continue;
+        }
+
+        let note = match (op.role, &op.timestamp) {
+            (Role::Sidecar, _) => String::from("(sidecar)"),
+            (Role::LiveVideo, _) => String::from("(live photo video)"),
+            (Role::Duplicate, _) => String::from("(duplicate)"),
+            (_, Some(timestamp)) => timestamp.to_string(),
+            (_, None) => String::from("(no timestamp)"),
+        };
+        let motion = if op.motion { " (motion photo)" } else { "" };
+        let copy = match op.action {
+            Action::Copy => " (copy)",
+            Action::Link => " (hard link)",
+            _ => "",
+        };
+        match op.role {
+            Role::Main | Role::Duplicate => println!("{from} -> {to}  {note}{motion}{copy}"),
+            _ => println!("  + {from} -> {to}  {note}{motion}{copy}"),
+        }
+    }
+}
+
+/// Shows the applied plan.
+pub fn print_applied(plan: &SortPlan) {
+    for op in plan.ops.iter() {
+        let verb = match (op.action, plan.kind) {
+            (Action::Rename, Kind::Rename) => "Renamed",
+            (Action::Rename, Kind::Revert) => "Reverted",
+            (Action::Copy, _) => "Copied",
+            (Action::Link, _) => "Linked",
+            (Action::RemoveCopy, _) => "Removed",
+            (Action::ReplaceWithLink, _) => "Replaced",
+        };
+        match op.action {
+            Action::RemoveCopy => println!("{verb}: {}", op.show(&op.from)),
+            Action::ReplaceWithLink => println!(
+                "{verb}: {} (hard link to {})",
+                op.show(&op.from),
+                op.show(&op.to)
+            ),
+            _ => println!("{verb}: {} -> {}", op.show(&op.from), op.show(&op.to)),
+        }
+    }
+}
+
+/// Shows the progress in stderr for large directories.
+pub fn print_progress(label: &str, done: usize, total: usize) {
+    if total < PROGRESS_THRESHOLD || !io::stderr().is_terminal() {
+        return;
+    }
+    if done.is_multiple_of(50) || done == total {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r{label}: {done}/{total}");
+        if done == total {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::Result,
+    group::group_by_dir,
+    journal::Journals,
+    plan::{Action, Kind, RenameOp, SortPlan},
+    sidecar::list_sidecars,
+    sort::strip_prefix,
+};
+
+/// Plans reverting renamed files by the journals of the root and the directories of the files.
+/// Falls back to cutting the prefix by `delim` in directories without a journal.
+pub fn plan_revert(files: Vec<PathBuf>, root: &Path, delim: &str) -> Result<SortPlan> {
+    let mut plan = SortPlan::new(Kind::Revert);
+    let mut journals = Journals::new(root);
+
+    let mut dirs = BTreeSet::from([PathBuf::from(root)]);
+    for file in files.iter() {
+        dirs.extend(
+            file.ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(root))
+                .map(PathBuf::from),
+        );
+    }
+    for dir in dirs.iter() {
+        let Some(entries) = journals.entries(dir)? else {
+            continue;
+        };
+        for entry in entries.iter().rev() {
+            let mut op = RenameOp::new(dir.join(&entry.to), dir.join(&entry.from));
+            op.base = Some(dir.clone());
+            if fs::symlink_metadata(&op.from).is_err() {
+                plan.notes
+                    .push(format!("Not found: {}", entry.to.to_string_lossy()));
+                continue;
+            }
+            if entry.copy {
+                // Removing the copy would lose the photo.
+                if fs::symlink_metadata(&op.to).is_err() {
+                    plan.notes.push(format!(
+                        "Kept: {} (source is missing)",
+                        entry.to.to_string_lossy()
+                    ));
+                    continue;
+                }
+                op.action = Action::RemoveCopy;
+            }
+            plan.push(op);
+        }
+    }
+
+    for (dir, files) in group_by_dir(files) {
+        if journals.entries(&dir)?.is_some() {
+            continue;
+        }
+        for file in files.iter() {
+            if journals.find(file)?.is_some() {
+                continue;
+            }
+            match revert_op(file, delim) {
+                Some(op) => plan.push(op),
+                None => plan.notes.push(format!(
+                    "Not processed: {}",
+                    file.file_name().unwrap().to_string_lossy()
+                )),
+            }
+        }
+        for file in list_sidecars([dir.as_path()]).iter() {
+            if journals.find(file)?.is_some() {
+                continue;
+            }
+            if let Some(op) = revert_op(file, delim) {
+                plan.push(op);
+            }
+        }
+    }
+    Ok(plan)
+}
+
+fn revert_op(file: &Path, delim: &str) -> Option<RenameOp> {
+    let org = file.file_name().unwrap().to_string_lossy();
+    let new_name = strip_prefix(&org, delim)?;
+
+    Some(RenameOp::new(file, file.with_file_name(new_name)))
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::{
+    duplicate::DUPLICATES_DIR,
+    error::{Error, Result},
+    formats::Format,
+};
+
+/// Which files are listed by `scan`.
+pub struct ScanOptions {
+    /// Extensions of files to be sorted, in lower case.
+    pub extensions: Vec<String>,
+    /// Detects files by magic bytes instead of extensions.
+    pub magic: bool,
+    /// Max depth of sub directories. 1 lists the directory only.
+    pub max_depth: usize,
+    pub follow_symlinks: bool,
+    /// Includes hidden directories.
+    pub hidden: bool,
+}
+
+/// Lists photos and videos under the root, sorted by name in each directory.
+/// The `duplicates` folder of the root is not listed.
+pub fn scan<P: AsRef<Path>>(root: P, opts: &ScanOptions) -> Result<Vec<PathBuf>> {
+    let root = root.as_ref();
+    fs::read_dir(root).map_err(Error::io("list files in", root))?;
+
+    let walker = WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(opts.max_depth)
+        .follow_links(opts.follow_symlinks)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            (opts.hidden || entry.depth() == 0 || !is_hidden_dir(entry))
+                && !is_duplicates_dir(entry)
+        });
+
+    let mut images = Vec::new();
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.into_path();
+        let is_image = if opts.magic {
+            Format::detect(&path).is_some()
+        } else {
+            path.extension()
+                .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+                .is_some_and(|ext| opts.extensions.contains(&ext))
+        };
+        if is_image {
+            images.push(path);
+        }
+    }
+
+    Ok(images)
+}
+
+fn is_hidden_dir(entry: &DirEntry) -> bool {
+    entry.file_type().is_dir() && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// Folder of the root that duplicates are moved into, which are out of the numbering.
+fn is_duplicates_dir(entry: &DirEntry) -> bool {
+    entry.depth() == 1 && entry.file_type().is_dir() && entry.file_name() == DUPLICATES_DIR
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn options(max_depth: usize, hidden: bool, follow_symlinks: bool) -> ScanOptions {
+        ScanOptions {
+            extensions: vec![String::from("jpg")],
+            magic: false,
+            max_depth,
+            follow_symlinks,
+            hidden,
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "a.jpg",
+            "b.txt",
+            "sub/c.jpg",
+            "sub/deep/d.jpg",
+            ".hidden/e.jpg",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("a.jpg"), root.join("link.jpg")).unwrap();
+            std::os::unix::fs::symlink(root.join("sub/deep"), root.join("linked")).unwrap();
+        }
+        let list = |opts: &ScanOptions| -> Vec<String> {
+            scan(root, opts)
+                .unwrap()
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect()
+        };
+
+        assert_eq!(list(&options(1, false, false)), vec!["a.jpg"]);
+        assert_eq!(list(&options(2, false, false)), vec!["a.jpg", "sub/c.jpg"]);
+        assert_eq!(
+            list(&options(usize::MAX, false, false)),
+            vec!["a.jpg", "sub/c.jpg", "sub/deep/d.jpg"]
+        );
+        assert_eq!(
+            list(&options(usize::MAX, true, false)),
+            vec![".hidden/e.jpg", "a.jpg", "sub/c.jpg", "sub/deep/d.jpg"]
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            list(&options(usize::MAX, false, true)),
+            vec![
+                "a.jpg",
+                "link.jpg",
+                "linked/d.jpg",
+                "sub/c.jpg",
+                "sub/deep/d.jpg"
+            ]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{group::Item, timestamp::CaptureTime};
+
+/// Extensions of sidecar files, in lower case.
+pub const SIDECAR_EXTENSIONS: &[&str] = &["xmp", "aae", "thm", "json"];
+/// XMP properties of the date taken, in order of preference.
+const XMP_DATE_PROPERTIES: &[&str] = &[
+    "exif:DateTimeOriginal",
+    "photoshop:DateCreated",
+    "xmp:CreateDate",
+];
+
+/// Lists sidecar files in the directories.
+pub fn list_sidecars<'a, I: IntoIterator<Item = &'a Path>>(dirs: I) -> Vec<PathBuf> {
+    let dirs: BTreeSet<&Path> = dirs.into_iter().collect();
+    let mut sidecars = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+            .map(|entry| entry.path())
+            .filter(|path| is_sidecar(path))
+            .collect();
+        files.sort();
+        sidecars.extend(files);
+    }
+    sidecars
+}
+
+/// Checks whether the file is a sidecar by its extension.
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| SIDECAR_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Attaches sidecars to the items they belong to.
+///
+/// A sidecar belongs to a photo if it is named after the whole name of the photo
+/// (`IMG_1234.jpg.xmp`, `IMG_1234.jpg.supplemental-metadata.json`) or after its stem
+/// (`IMG_1234.xmp`). Names are compared by the original names, ignoring case.
+pub fn attach_sidecars(
+    items: &mut [Item],
+    sidecars: &[PathBuf],
+    originals: &HashMap<PathBuf, String>,
+) {
+    let mut names: HashMap<(PathBuf, String), usize> = HashMap::new();
+    let mut stems: HashMap<(PathBuf, String), usize> = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        for photo in item.photos.iter() {
+            let dir = parent(&photo.path);
+            let name = originals[&photo.path].to_lowercase();
+            let stem = stem(&name);
+            stems
+                .entry((dir.clone(), stem.to_string()))
+                .or_insert(index);
+            names.entry((dir, name)).or_insert(index);
+        }
+    }
+
+    for sidecar in sidecars.iter() {
+        let dir = parent(sidecar);
+        let name = originals[sidecar].to_lowercase();
+        // Prefixes of the name at each dot, longest first.
+        let prefixes: Vec<&str> = name.rmatch_indices('.').map(|(i, _)| &name[..i]).collect();
+        let found = prefixes
+            .iter()
+            .find_map(|prefix| names.get(&(dir.clone(), prefix.to_string())))
+            .or_else(|| {
+                prefixes
+                    .iter()
+                    .find_map(|prefix| stems.get(&(dir.clone(), prefix.to_string())))
+            });
+        if let Some(&index) = found {
+            items[index].sidecars.push(sidecar.clone());
+        }
+    }
+}
+
+/// New name of a sidecar, following the new name of the photo it belongs to.
+///
+/// `photos` are pairs of the original and the new names of the photos in the item.
+/// `IMG_1234.jpg.xmp` follows the whole name and `IMG_1234.xmp` follows the stem.
+pub fn sidecar_name(sidecar: &str, photos: &[(&str, &str)]) -> Option<String> {
+    let follows = |prefix: &str| {
+        sidecar
+            .get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+            && sidecar[prefix.len()..].starts_with('.')
+    };
+    let by_name = photos
+        .iter()
+        .find(|(org, _)| follows(org))
+        .map(|(org, new)| format!("{new}{}", &sidecar[org.len()..]));
+    by_name.or_else(|| {
+        photos.iter().find_map(|(org, new)| {
+            let org = stem(org);
+            follows(org).then(|| format!("{}{}", stem(new), &sidecar[org.len()..]))
+        })
+    })
+}
+
+/// Reads the date taken from the XMP or Google Takeout JSON sidecar of the photo.
+///
+/// XMP sidecars are `IMG_1234.jpg.xmp` or `IMG_1234.xmp`. JSON sidecars are
+/// `IMG_1234.jpg.json` or `IMG_1234.jpg.supplemental-metadata.json`, whose
+/// `photoTakenTime` is in UTC.
+pub fn sidecar_time(path: &Path) -> Option<CaptureTime> {
+    let name = path.file_name()?.to_string_lossy();
+    let stem = stem(&name);
+    let read = |sidecar: String| fs::read_to_string(path.with_file_name(sidecar)).ok();
+
+    let xmp = [&*name, stem]
+        .iter()
+        .flat_map(|prefix| [format!("{prefix}.xmp"), format!("{prefix}.XMP")])
+        .find_map(|sidecar| read(sidecar).and_then(|xmp| xmp_time(&xmp)));
+    xmp.or_else(|| {
+        [".json", ".supplemental-metadata.json"]
+            .iter()
+            .find_map(|suffix| read(format!("{name}{suffix}")).and_then(|json| json_time(&json)))
+    })
+}
+
+/// Finds the date taken in XMP, written either as an attribute or as an element.
+fn xmp_time(xmp: &str) -> Option<CaptureTime> {
+    XMP_DATE_PROPERTIES.iter().find_map(|property| {
+        let attribute = xmp
+            .split_once(&format!("{property}=\""))
+            .and_then(|(_, rest)| rest.split_once('"'));
+        let element = xmp
+            .split_once(&format!("<{property}>"))
+            .and_then(|(_, rest)| rest.split_once('<'));
+        attribute
+            .or(element)
+            .and_then(|(value, _)| parse_iso8601(value))
+    })
+}
+
+/// Reads `photoTakenTime` of Google Takeout, in seconds since the epoch.
+fn json_time(json: &str) -> Option<CaptureTime> {
+    let json: serde_json::Value = serde_json::from_str(json).ok()?;
+    let seconds = &json["photoTakenTime"]["timestamp"];
+    let seconds = match seconds.as_str() {
+        Some(seconds) => seconds.parse().ok()?,
+        None => seconds.as_i64()?,
+    };
+    Some(CaptureTime {
+        local: DateTime::from_timestamp(seconds, 0)?.naive_utc(),
+        offset: FixedOffset::east_opt(0),
+    })
+}
+
+/// Parses XMP dates like `2024-05-01T10:00:00.5+09:00`. Seconds and the offset may be left out.
+fn parse_iso8601(value: &str) -> Option<CaptureTime> {
+    let value = value.trim();
+    let (local, offset) = if let Some(local) = value.strip_suffix('Z') {
+        (local, FixedOffset::east_opt(0))
+    } else {
+        match value
+            .len()
+            .checked_sub(6)
+            .and_then(|i| value.split_at_checked(i))
+        {
+            Some((local, offset)) if offset.starts_with(['+', '-']) => {
+                (local, Some(offset.parse::<FixedOffset>().ok()?))
+            }
+            _ => (value, None),
+        }
+    };
+    let local = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(local, format).ok())?;
+    Some(CaptureTime { local, offset })
+}
+
+fn stem(name: &str) -> &str {
+    name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name)
+}
+
+fn parent(path: &Path) -> PathBuf {
+    path.parent().map(PathBuf::from).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{group::group_by_stem, metadata::Photo};
+
+    #[test]
+    fn test_attach_sidecars() {
+        let photos = ["IMG_1.JPG", "IMG_2.HEIC"];
+        let sidecars: Vec<PathBuf> = ["IMG_1.xmp", "IMG_2.HEIC.json", "IMG_2.aae", "IMG_3.xmp"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let originals: HashMap<PathBuf, String> = photos
+            .iter()
+            .map(PathBuf::from)
+            .chain(sidecars.iter().cloned())
+            .map(|path| (path.clone(), path.to_string_lossy().to_string()))
+            .collect();
+        let photos = photos
+            .iter()
+            .map(|f| Photo {
+                path: PathBuf::from(f),
+                ..Default::default()
+            })
+            .collect();
+
+        let mut items = group_by_stem(photos, &originals, true);
+        attach_sidecars(&mut items, &sidecars, &originals);
+        assert_eq!(items[0].sidecars, vec![PathBuf::from("IMG_1.xmp")]);
+        assert_eq!(
+            items[1].sidecars,
+            vec![PathBuf::from("IMG_2.HEIC.json"), PathBuf::from("IMG_2.aae")]
+        );
+
+        let photos = [("IMG_2.HEIC", "001_x.heic"), ("IMG_2.MOV", "001_x.mov")];
+        assert_eq!(
+            sidecar_name("IMG_2.HEIC.json", &photos).as_deref(),
+            Some("001_x.heic.json")
+        );
+        assert_eq!(
+            sidecar_name("img_2.aae", &photos).as_deref(),
+            Some("001_x.aae")
+        );
+    }
+
+    #[test]
+    fn test_sidecar_time() {
+        let xmp = r#"<rdf:Description exif:DateTimeOriginal="2024-05-01T10:00:00.5+09:00"/>"#;
+        let time = xmp_time(xmp).unwrap();
+        assert_eq!(time.to_string(), "2024-05-01 10:00:00.500+09:00");
+        let xmp = "<photoshop:DateCreated>2024-05-01T10:00</photoshop:DateCreated>";
+        assert_eq!(
+            xmp_time(xmp).unwrap().to_string(),
+            "2024-05-01 10:00:00.000"
+        );
+
+        let json = r#"{"photoTakenTime": {"timestamp": "1714557600", "formatted": "..."}}"#;
+        let time = json_time(json).unwrap();
+        assert_eq!(time.to_string(), "2024-05-01 10:00:00.000+00:00");
+    }
+}
@@ -0,0 +1,245 @@
+use clap::ValueEnum;
+use exif::{Exif, In, Tag};
+use image::{imageops::FilterType, DynamicImage, ImageReader};
+use std::{
+    f64::consts::PI,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    formats::read_exif,
+    metadata::{map_files, Progress},
+};
+
+/// Perceptual hash that stays close when a photo is resized or recompressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HashAlgorithm {
+    /// Average hash: pixels brighter than the mean of 8x8 gray image
+    Ahash,
+    /// Difference hash: gradients between neighbors of 9x8 gray image
+    Dhash,
+    /// Perceptual hash: low frequencies of DCT of 32x32 gray image
+    Phash,
+}
+
+/// One of similar photos.
+pub struct Member {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct Fingerprint {
+    hash: u64,
+    width: u32,
+    height: u32,
+}
+
+impl HashAlgorithm {
+    fn hash(self, image: &DynamicImage) -> u64 {
+        match self {
+            Self::Ahash => {
+                let pixels = gray(image, 8, 8);
+                let mean = pixels.iter().sum::<f64>() / pixels.len() as f64;
+                bits(pixels.iter().map(|p| *p > mean))
+            }
+            Self::Dhash => {
+                let pixels = gray(image, 9, 8);
+                bits(
+                    pixels
+                        .chunks(9)
+                        .flat_map(|row| row.windows(2).map(|pair| pair[0] > pair[1])),
+                )
+            }
+            Self::Phash => {
+                let pixels = gray(image, 32, 32);
+                let coefficients = dct_low(&pixels);
+                // The first coefficient is the mean, which says nothing about the content.
+                let mut sorted = coefficients[1..].to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let median = sorted[sorted.len() / 2];
+                bits(coefficients.iter().map(|c| *c > median))
+            }
+        }
+    }
+}
+
+impl Member {
+    pub fn pixels(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
+/// Finds clusters of similar photos, whose hashes differ by `max_distance` bits or less.
+///
+/// Files that can not be decoded and have no Exif thumbnail are left out.
+/// Members of a cluster are ordered by resolution, highest first.
+pub fn find_similar(
+    files: &[PathBuf],
+    algorithm: HashAlgorithm,
+    max_distance: u32,
+    jobs: usize,
+    progress: Option<&Progress>,
+) -> Vec<Vec<Member>> {
+    let fingerprints = map_files(files, jobs, "Hashing images", progress, |file| {
+        fingerprint(file, algorithm)
+    });
+    let hashed: Vec<(usize, &Fingerprint)> = fingerprints
+        .iter()
+        .enumerate()
+        .filter_map(|(index, fingerprint)| fingerprint.as_ref().map(|f| (index, f)))
+        .collect();
+
+    let mut parents: Vec<usize> = (0..files.len()).collect();
+    for (i, (index, fingerprint)) in hashed.iter().enumerate() {
+        for (other, other_fingerprint) in hashed[i + 1..].iter() {
+            if (fingerprint.hash ^ other_fingerprint.hash).count_ones() <= max_distance {
+                let (a, b) = (root(&mut parents, *index), root(&mut parents, *other));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<Member>> = Vec::new();
+    let mut cluster_of = vec![None; files.len()];
+    for (index, fingerprint) in hashed {
+        let root = root(&mut parents, index);
+        let cluster = *cluster_of[root].get_or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[cluster].push(Member {
+            path: files[index].clone(),
+            width: fingerprint.width,
+            height: fingerprint.height,
+        });
+    }
+
+    clusters.retain(|members| members.len() > 1);
+    for members in clusters.iter_mut() {
+        members.sort_by_key(|member| std::cmp::Reverse(member.pixels()));
+    }
+    clusters
+}
+
+/// Hashes the decoded image, or the Exif thumbnail of formats that can not be decoded, e.g. RAW.
+fn fingerprint(path: &Path, algorithm: HashAlgorithm) -> Option<Fingerprint> {
+    let decoded = ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode();
+    let (image, (width, height)) = match decoded {
+        Ok(image) => {
+            let size = (image.width(), image.height());
+            (image, size)
+        }
+        Err(_) => {
+            let exif = read_exif(path);
+            let image = thumbnail(&exif)?;
+            let size = exif_size(&exif).unwrap_or((image.width(), image.height()));
+            (image, size)
+        }
+    };
+
+    Some(Fingerprint {
+        hash: algorithm.hash(&image),
+        width,
+        height,
+    })
+}
+
+fn thumbnail(exif: &[Exif]) -> Option<DynamicImage> {
+    exif.iter().find_map(|exif| {
+        let uint = |tag| exif.get_field(tag, In::THUMBNAIL)?.value.get_uint(0);
+        let offset = uint(Tag::JPEGInterchangeFormat)? as usize;
+        let len = uint(Tag::JPEGInterchangeFormatLength)? as usize;
+        let data = exif.buf().get(offset..offset.checked_add(len)?)?;
+        image::load_from_memory(data).ok()
+    })
+}
+
+fn exif_size(exif: &[Exif]) -> Option<(u32, u32)> {
+    let uint = |tag| {
+        exif.iter()
+            .find_map(|exif| exif.get_field(tag, In::PRIMARY)?.value.get_uint(0))
+    };
+    Some((uint(Tag::PixelXDimension)?, uint(Tag::PixelYDimension)?))
+}
+
+/// Resizes the image to gray pixels in row-major order.
+fn gray(image: &DynamicImage, width: u32, height: u32) -> Vec<f64> {
+    image
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_luma8()
+        .pixels()
+        .map(|pixel| pixel.0[0] as f64)
+        .collect()
+}
+
+/// 8x8 lowest frequencies of 2D DCT-II of 32x32 pixels.
+fn dct_low(pixels: &[f64]) -> Vec<f64> {
+    let cos: Vec<Vec<f64>> = (0..8)
+        .map(|u| {
+            (0..32)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * PI / 64.0).cos())
+                .collect()
+        })
+        .collect();
+    let mut coefficients = Vec::with_capacity(64);
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut sum = 0.0;
+            for y in 0..32 {
+                for x in 0..32 {
+                    sum += pixels[y * 32 + x] * cos[u][x] * cos[v][y];
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+    coefficients
+}
+
+fn bits<I: Iterator<Item = bool>>(bits: I) -> u64 {
+    bits.fold(0, |hash, bit| (hash << 1) | bit as u64)
+}
+
+fn root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_hash_distance() {
+        let scene = |width: u32, height: u32| {
+            DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+                let (x, y) = (x as f64 / width as f64, y as f64 / height as f64);
+                let value = (128.0 + 100.0 * (x * 7.0).sin() * (y * 5.0).cos()) as u8;
+                Rgb([value, (255.0 * x) as u8, (255.0 * y) as u8])
+            }))
+        };
+        let photo = scene(640, 480);
+        let resized = photo.resize_exact(160, 120, FilterType::Lanczos3);
+        let other = photo.fliph();
+
+        for algorithm in [
+            HashAlgorithm::Ahash,
+            HashAlgorithm::Dhash,
+            HashAlgorithm::Phash,
+        ] {
+            let hash = algorithm.hash(&photo);
+            let distance = |image| (hash ^ algorithm.hash(image)).count_ones();
+            assert!(distance(&resized) <= 4, "{algorithm:?}");
+            assert!(distance(&other) > 10, "{algorithm:?}");
+        }
+    }
+}
@@ -0,0 +1,785 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    burst::{burst_folder, is_burst_folder, split_bursts},
+    config::Config,
+    duplicate::{find_duplicates, Duplicate, DuplicateMode, DUPLICATES_DIR},
+    error::{invalid, Result},
+    event::{number_events, EventGap},
+    group::{group_by_stem, Item},
+    journal::{relative_path, Journals},
+    live::pair_live_photos,
+    metadata::{read_photos, Extras, Photo, Progress},
+    plan::{Action, Kind, RenameOp, Role, SortPlan},
+    sidecar::{attach_sidecars, is_sidecar, list_sidecars, sidecar_name},
+    similar::{find_similar, HashAlgorithm, Member},
+    source::TimeReader,
+    template::{Fields, Frame, Layout, Template},
+    timestamp::Timestamp,
+};
+
+/// How files are sorted and named by `plan`.
+pub struct SortOptions {
+    /// Directory to sort, whose journals know the original names.
+    pub root: PathBuf,
+    /// Delimiter after the number of default names.
+    pub delim: String,
+    /// Template of new names.
+    pub template: Template,
+    /// Folders to put files into, under the output directory.
+    pub layout: Option<Layout>,
+    /// Directory to put copies into, leaving the sources as is.
+    pub output: Option<PathBuf>,
+    /// How files are put into the layout or the output.
+    pub transfer: Transfer,
+    /// Where capture times are read from.
+    pub reader: TimeReader,
+    /// Camera clock offsets.
+    pub config: Config,
+    /// Number of threads to read files.
+    pub jobs: usize,
+    /// Sorts each directory on its own, unless files go to a layout or an output.
+    pub by_dir: bool,
+    /// Numbers files sharing a name stem (e.g. RAW+JPEG) and Live Photos as one.
+    pub group: bool,
+    /// Sorts latest to oldest.
+    pub desc: bool,
+    /// Gap that starts a new event.
+    pub event_gap: Option<EventGap>,
+    /// Numbers bursts and exposure brackets as one photo.
+    pub bursts: bool,
+    /// Moves each burst into its own folder.
+    pub burst_folders: bool,
+    /// What to do with byte-identical files.
+    pub duplicates: Option<DuplicateMode>,
+    /// Reports, or keeps only the best of, similar photos.
+    pub similar: Option<Similarity>,
+    /// Called as files are read and hashed.
+    pub progress: Option<Box<Progress<'static>>>,
+}
+
+/// How files are put into the layout or the output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    /// Renames files.
+    Move,
+    /// Copies verified by checksum.
+    Copy,
+    /// Hard links, on the same file system.
+    Link,
+}
+
+/// How similar photos are found.
+pub struct Similarity {
+    pub algorithm: HashAlgorithm,
+    /// Max number of different bits of the hashes.
+    pub max_distance: u32,
+    /// Numbers only the highest resolution photo of similar ones.
+    pub keep_best: bool,
+}
+
+impl SortOptions {
+    /// Root of the layout and the copies.
+    pub fn output_dir(&self) -> &Path {
+        self.output.as_deref().unwrap_or(&self.root)
+    }
+}
+
+/// Splits photos into units that are sorted and numbered independently.
+fn group_files(
+    photos: Vec<Photo>,
+    options: &SortOptions,
+    originals: &Originals,
+) -> Vec<Vec<Photo>> {
+    if !options.by_dir || options.layout.is_some() || options.output.is_some() {
+        return vec![photos];
+    }
+    let mut groups: BTreeMap<PathBuf, Vec<Photo>> = BTreeMap::new();
+    for photo in photos {
+        let dir = originals.home_dir(&photo.path);
+        groups.entry(dir).or_default().push(photo);
+    }
+    groups.into_values().collect()
+}
+
+fn sort_by_time(t1: Option<&Timestamp>, t2: Option<&Timestamp>) -> Ordering {
+    match (t1, t2) {
+        (Some(t1), Some(t2)) => t1.time.cmp(&t2.time),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+/// Plans sorting and renaming the files listed by `scan`.
+pub fn plan(mut files: Vec<PathBuf>, options: &SortOptions) -> Result<SortPlan> {
+    let delim = options.delim.as_str();
+    let template = &options.template;
+    let layout_event = options
+        .layout
+        .as_ref()
+        .is_some_and(|layout| layout.uses_event());
+    if options.event_gap.is_none() && (template.uses_event() || layout_event) {
+        invalid!("{{event}} needs --event-gap.");
+    }
+    let duplicates = match options.duplicates {
+        Some(DuplicateMode::Link) if options.output.is_some() => {
+            invalid!(
+                "--duplicates link replaces files in place, and can not be used with --output."
+            )
+        }
+        Some(mode) => {
+            let duplicates = find_duplicates(&files)?;
+            if mode != DuplicateMode::Report {
+                let paths: HashSet<&PathBuf> = duplicates.iter().map(|dup| &dup.path).collect();
+                files.retain(|file| !paths.contains(file));
+            }
+            duplicates
+        }
+        None => Vec::new(),
+    };
+    let mut notes = Vec::new();
+    if let Some(similar) = &options.similar {
+        let clusters = find_similar(
+            &files,
+            similar.algorithm,
+            similar.max_distance,
+            options.jobs,
+            options.progress.as_deref(),
+        );
+        for members in clusters.iter() {
+            notes.push(similar_note(members, options));
+        }
+        if similar.keep_best {
+            let others: HashSet<&PathBuf> = clusters
+                .iter()
+                .flat_map(|members| members[1..].iter().map(|member| &member.path))
+                .collect();
+            files.retain(|file| !others.contains(file));
+        }
+    }
+    let mut journals = Journals::new(&options.root);
+    add_burst_files(&mut files, &options.root, &mut journals)?;
+    let sidecars = list_sidecars(files.iter().filter_map(|file| file.parent()));
+    let originals = original_names(files.iter().chain(sidecars.iter()), delim, &mut journals)?;
+    let names = &originals.names;
+    let extras = Extras {
+        live: options.group,
+        camera: options.bursts
+            || !options.config.cameras.is_empty()
+            || options.template.uses_camera()
+            || options
+                .layout
+                .as_ref()
+                .is_some_and(|layout| layout.uses_camera()),
+        shot: options.bursts,
+    };
+    let mut photos = read_photos(
+        files,
+        &options.reader,
+        extras,
+        options.jobs,
+        options.progress.as_deref(),
+    );
+    for photo in photos.iter_mut() {
+        options.config.correct(photo);
+    }
+
+    let mut plan = SortPlan::new(Kind::Rename);
+    plan.notes = notes;
+    for duplicate in duplicates.iter() {
+        plan_duplicate(&mut plan, duplicate, &originals, options);
+    }
+    for photos in group_files(photos, options, &originals) {
+        let mut items = group_by_stem(photos, names, options.group);
+        if options.group {
+            items = pair_live_photos(items);
+        }
+        attach_sidecars(&mut items, &sidecars, names);
+        items.sort_by(|i1, i2| sort_by_time(i1.timestamp(), i2.timestamp()));
+        if options.desc {
+            items.reverse();
+        }
+
+        let events = match options.event_gap {
+            Some(gap) => number_events(&items, gap.0),
+            None => vec![1; items.len()],
+        };
+        let event_width = get_prefix_len(events.last().copied().unwrap_or_default());
+        let items: Events = events.into_iter().zip(items).collect();
+
+        for (leaf, items) in split_by_layout(items, options, names, event_width)? {
+            // Numbers restart in each event.
+            for items in items.chunk_by(|(e1, _), (e2, _)| e1 == e2) {
+                let event = items[0].0;
+                let items = items.iter().map(|(_, item)| item);
+                let bursts = match options.bursts {
+                    true => split_bursts(items),
+                    false => items.map(|item| vec![item]).collect(),
+                };
+                let seq_width = get_prefix_len(bursts.len());
+                for (index, burst) in bursts.iter().enumerate() {
+                    let seq = index + 1;
+                    let folder = (options.burst_folders && burst.len() > 1).then(|| {
+                        let event = options.event_gap.map(|_| event);
+                        burst_folder(event, seq, seq_width)
+                    });
+                    let target = |file: &Path, name: &str| {
+                        let dir = match &leaf {
+                            Some(dir) => dir.clone(),
+                            None => originals.home_dir(file),
+                        };
+                        match &folder {
+                            Some(folder) => dir.join(folder).join(name),
+                            None => dir.join(name),
+                        }
+                    };
+                    for (frame, item) in burst.iter().enumerate() {
+                        let number = Number {
+                            seq,
+                            seq_width,
+                            frame: (burst.len() > 1).then_some(Frame {
+                                index: frame,
+                                count: burst.len(),
+                            }),
+                            event,
+                            event_width,
+                        };
+                        plan_item(
+                            &mut plan, item, &number, &target, template, &originals, options,
+                        )?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Number of an item in its folder.
+struct Number {
+    seq: usize,
+    seq_width: usize,
+    frame: Option<Frame>,
+    event: usize,
+    event_width: usize,
+}
+
+/// Plans renaming the files of one item by the template, followed by its sidecars.
+fn plan_item<F: Fn(&Path, &str) -> PathBuf>(
+    plan: &mut SortPlan,
+    item: &Item,
+    number: &Number,
+    target: &F,
+    template: &Template,
+    originals: &Originals,
+    options: &SortOptions,
+) -> Result<()> {
+    let names = &originals.names;
+    let timestamp = item.timestamp().copied();
+    let mut new_names = Vec::new();
+    for (i, photo) in item.photos.iter().enumerate() {
+        let file = &photo.path;
+        let name = template.render(&Fields {
+            seq: number.seq,
+            seq_width: number.seq_width,
+            frame: number.frame,
+            event: number.event,
+            event_width: number.event_width,
+            timestamp: timestamp.as_ref(),
+            camera: &item.photos[0].camera,
+            name: &names[file],
+            path: file,
+        })?;
+        let mut op = RenameOp::new(file, target(file, &name));
+        op.role = match i {
+            0 => Role::Main,
+            _ if photo.is_video() => Role::LiveVideo,
+            _ => Role::Pair,
+        };
+        op.timestamp = photo.timestamp;
+        op.motion = photo.motion;
+        set_destination(&mut op, originals, options);
+        plan.push(op);
+        new_names.push((names[file].as_str(), name));
+    }
+
+    let new_names: Vec<(&str, &str)> = new_names
+        .iter()
+        .map(|(org, new)| (*org, new.as_str()))
+        .collect();
+    for file in item.sidecars.iter() {
+        let Some(name) = sidecar_name(&names[file], &new_names) else {
+            continue;
+        };
+        let mut op = RenameOp::new(file, target(file, &name));
+        op.role = Role::Sidecar;
+        set_destination(&mut op, originals, options);
+        plan.push(op);
+    }
+    Ok(())
+}
+
+/// Items with their event numbers.
+type Events = Vec<(usize, Item)>;
+
+/// Splits sorted items into the folders of the layout, keeping the order in each folder.
+/// Without a layout, the items go to the output directory, or stay in their own directories.
+fn split_by_layout(
+    items: Events,
+    options: &SortOptions,
+    names: &HashMap<PathBuf, String>,
+    event_width: usize,
+) -> Result<Vec<(Option<PathBuf>, Events)>> {
+    let Some(layout) = &options.layout else {
+        let leaf = options.output.clone();
+        return Ok(vec![(leaf, items)]);
+    };
+
+    let mut leaves: BTreeMap<PathBuf, Events> = BTreeMap::new();
+    for (event, item) in items {
+        let photo = &item.photos[0];
+        let folder = layout.render(&Fields {
+            seq: 0,
+            seq_width: 0,
+            frame: None,
+            event,
+            event_width,
+            timestamp: item.timestamp(),
+            camera: &photo.camera,
+            name: &names[&photo.path],
+            path: &photo.path,
+        })?;
+        leaves
+            .entry(options.output_dir().join(folder))
+            .or_default()
+            .push((event, item));
+    }
+    Ok(leaves
+        .into_iter()
+        .map(|(dir, items)| (Some(dir), items))
+        .collect())
+}
+
+/// Reports the duplicate, or plans moving or linking it by the mode.
+fn plan_duplicate(
+    plan: &mut SortPlan,
+    duplicate: &Duplicate,
+    originals: &Originals,
+    options: &SortOptions,
+) {
+    let path = relative_path(&options.root, &duplicate.path);
+    let original = relative_path(&options.root, &duplicate.original);
+    match options.duplicates {
+        Some(DuplicateMode::Report) => plan.notes.push(format!(
+            "Duplicate: {} (same as {})",
+            path.to_string_lossy(),
+            original.to_string_lossy()
+        )),
+        Some(DuplicateMode::Skip) => plan.notes.push(format!(
+            "Skipped: {} (same as {})",
+            path.to_string_lossy(),
+            original.to_string_lossy()
+        )),
+        Some(DuplicateMode::Move) => {
+            // Keeps the folders, so that duplicates of the same name do not collide.
+            let relative = match duplicate.path.strip_prefix(&options.root) {
+                Ok(relative) => relative,
+                Err(_) => Path::new(duplicate.path.file_name().unwrap()),
+            };
+            let to = options.output_dir().join(DUPLICATES_DIR).join(relative);
+            let mut op = RenameOp::new(&duplicate.path, to);
+            op.role = Role::Duplicate;
+            set_destination(&mut op, originals, options);
+            // Recorded in the directory that has the folder, so that revert moves it back.
+            op.base = Some(PathBuf::from(options.output_dir()));
+            plan.push(op);
+        }
+        Some(DuplicateMode::Link) => {
+            let mut op = RenameOp::new(&duplicate.path, &duplicate.original);
+            op.action = Action::ReplaceWithLink;
+            op.base = Some(PathBuf::from(&options.root));
+            plan.push(op);
+        }
+        None => {}
+    }
+}
+
+/// Lists similar photos, highest resolution first.
+fn similar_note(members: &[Member], options: &SortOptions) -> String {
+    let keep_best = options
+        .similar
+        .as_ref()
+        .is_some_and(|similar| similar.keep_best);
+    let mut note = String::from("Similar:");
+    for (index, member) in members.iter().enumerate() {
+        let skipped = if keep_best && index > 0 {
+            "  (skipped)"
+        } else {
+            ""
+        };
+        note.push_str(&format!(
+            "\n  {}  {}x{}{skipped}",
+            relative_path(&options.root, &member.path).to_string_lossy(),
+            member.width,
+            member.height
+        ));
+    }
+    note
+}
+
+/// Sets the journal that records the op, and whether the file is copied.
+/// Files put into the layout or the output are recorded in the output directory,
+/// so that they are undone together.
+fn set_destination(op: &mut RenameOp, originals: &Originals, options: &SortOptions) {
+    if options.layout.is_some() || options.output.is_some() {
+        op.base = Some(PathBuf::from(options.output_dir()));
+        op.action = match options.transfer {
+            Transfer::Move => Action::Rename,
+            Transfer::Copy => Action::Copy,
+            Transfer::Link => Action::Link,
+        };
+    } else {
+        op.base = originals.bases.get(&op.from).cloned();
+    }
+}
+
+/// Original names of files, and the directories of the journals that know them.
+struct Originals {
+    names: HashMap<PathBuf, String>,
+    bases: HashMap<PathBuf, PathBuf>,
+}
+
+impl Originals {
+    /// Directory the file is sorted in. Files moved into burst folders belong to the
+    /// directory of the folder, so that they are numbered and named there again.
+    fn home_dir(&self, file: &Path) -> PathBuf {
+        let parent = file.parent().unwrap_or(Path::new(""));
+        match (parent.file_name(), parent.parent()) {
+            (Some(folder), Some(dir))
+                if is_burst_folder(&folder.to_string_lossy())
+                    && self.bases.get(file).is_some_and(|base| base == dir) =>
+            {
+                PathBuf::from(dir)
+            }
+            _ => PathBuf::from(parent),
+        }
+    }
+}
+
+/// Adds files in burst folders known from the journals of the root and the directories
+/// of the files. Scans without `--recursive` do not list them, but they still have numbers.
+fn add_burst_files(files: &mut Vec<PathBuf>, root: &Path, journals: &mut Journals) -> Result<()> {
+    let mut dirs: Vec<PathBuf> = files
+        .iter()
+        .filter_map(|file| file.parent().map(PathBuf::from))
+        .collect();
+    dirs.push(PathBuf::from(root));
+    dirs.sort();
+    dirs.dedup();
+
+    let listed: HashSet<PathBuf> = files.iter().cloned().collect();
+    for dir in dirs {
+        let Some(entries) = journals.entries(&dir)? else {
+            continue;
+        };
+        for entry in entries.iter().filter(|entry| !entry.copy) {
+            let in_folder = entry.to.parent().is_some_and(|folder| {
+                folder.components().count() == 1 && is_burst_folder(&folder.to_string_lossy())
+            });
+            let file = dir.join(&entry.to);
+            if in_folder && !is_sidecar(&file) && !listed.contains(&file) && file.is_file() {
+                files.push(file);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn original_names<'a, I: IntoIterator<Item = &'a PathBuf>>(
+    files: I,
+    delim: &str,
+    journals: &mut Journals,
+) -> Result<Originals> {
+    let mut originals = Originals {
+        names: HashMap::new(),
+        bases: HashMap::new(),
+    };
+
+    for file in files {
+        let name = file.file_name().unwrap().to_string_lossy();
+        let org = match journals.find(file)? {
+            Some((dir, entry)) => {
+                originals.bases.insert(file.clone(), dir);
+                entry
+                    .from
+                    .file_name()
+                    .map(|org| org.to_string_lossy().to_string())
+                    .unwrap_or_else(|| name.to_string())
+            }
+            None if journals.entries(file.parent().unwrap())?.is_some() => name.to_string(),
+            None => strip_prefix(&name, delim).unwrap_or(&name).to_string(),
+        };
+        originals.names.insert(file.clone(), org);
+    }
+
+    Ok(originals)
+}
+
+/// Strips `NNN<delim>` prefix from the file name.
+pub fn strip_prefix<'a>(name: &'a str, delim: &str) -> Option<&'a str> {
+    let (prefix, rest) = name.split_once(delim)?;
+    if prefix.is_empty() || rest.is_empty() || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(rest)
+}
+
+pub fn get_prefix_len(files_len: usize) -> usize {
+    let files_len = files_len.to_string();
+    files_len.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        filename::FilenamePattern,
+        journal::DATA_DIR,
+        revert::plan_revert,
+        scan::{scan, ScanOptions},
+        source::TimeSource,
+        video::make_box,
+    };
+    use chrono::{Local, NaiveDate, TimeZone};
+    use std::fs;
+    use walkdir::WalkDir;
+
+    fn options(root: &Path) -> SortOptions {
+        SortOptions {
+            root: PathBuf::from(root),
+            delim: String::from("__"),
+            template: Template::prefix("__", false),
+            layout: None,
+            output: None,
+            transfer: Transfer::Move,
+            reader: TimeReader::new(&[TimeSource::Filename], &FilenamePattern::builtin()),
+            config: Config::default(),
+            jobs: 1,
+            by_dir: false,
+            group: true,
+            desc: false,
+            event_gap: None,
+            bursts: false,
+            burst_folders: false,
+            duplicates: None,
+            similar: None,
+            progress: None,
+        }
+    }
+
+    /// Lists JPEGs under the root, recursively or not.
+    fn scan_jpg(root: &Path, recursive: bool) -> Vec<PathBuf> {
+        let opts = ScanOptions {
+            extensions: vec![String::from("jpg")],
+            magic: false,
+            max_depth: if recursive { usize::MAX } else { 1 },
+            follow_symlinks: false,
+            hidden: false,
+        };
+        scan(root, &opts).unwrap()
+    }
+
+    /// Paths of all files and folders under the root, without the journals.
+    fn list(root: &Path) -> Vec<String> {
+        WalkDir::new(root)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != DATA_DIR)
+            .map(|entry| {
+                let path = entry.unwrap().into_path();
+                path.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_layout_revert() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let names = [
+            "IMG_20230514_081522.jpg",
+            "IMG_20230514_091000.jpg",
+            "IMG_20230601_120000.jpg",
+        ];
+        for name in names {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        let options = SortOptions {
+            layout: Some("YYYY/MM/DD".parse().unwrap()),
+            ..options(root)
+        };
+        crate::apply(&plan(scan_jpg(root, false), &options).unwrap()).unwrap();
+        assert_eq!(
+            list(root),
+            vec![
+                "2023",
+                "2023/05",
+                "2023/05/14",
+                "2023/05/14/1__IMG_20230514_081522.jpg",
+                "2023/05/14/2__IMG_20230514_091000.jpg",
+                "2023/06",
+                "2023/06/01",
+                "2023/06/01/1__IMG_20230601_120000.jpg",
+            ]
+        );
+
+        let revert = plan_revert(scan_jpg(root, true), root, "__").unwrap();
+        crate::apply(&revert).unwrap();
+        assert_eq!(list(root), names);
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        assert_eq!(strip_prefix("003__IMG.jpg", "__"), Some("IMG.jpg"));
+        assert_eq!(strip_prefix("12-a__b.jpg", "-"), Some("a__b.jpg"));
+        assert_eq!(strip_prefix("IMG__003.jpg", "__"), None);
+        assert_eq!(strip_prefix("__IMG.jpg", "__"), None);
+        assert_eq!(strip_prefix("IMG.jpg", "__"), None);
+    }
+
+    #[test]
+    fn test_duplicates_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (file, content) in [
+            ("a/IMG_20230514_081522.jpg", "photo"),
+            ("a/IMG_20230514_091000.jpg", "other"),
+            ("b/IMG_20230514_081522.jpg", "photo"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+
+        let options = SortOptions {
+            duplicates: Some(DuplicateMode::Move),
+            ..options(root)
+        };
+        crate::apply(&plan(scan_jpg(root, true), &options).unwrap()).unwrap();
+        let sorted = vec![
+            "a",
+            "a/1__IMG_20230514_081522.jpg",
+            "a/2__IMG_20230514_091000.jpg",
+            "duplicates",
+            "duplicates/b",
+            "duplicates/b/IMG_20230514_081522.jpg",
+        ];
+        assert_eq!(list(root), sorted);
+
+        // Moved duplicates are not numbered again.
+        let plan = plan(scan_jpg(root, true), &options).unwrap();
+        assert!(plan.ops.is_empty());
+    }
+
+    #[test]
+    fn test_burst_folders_rerun() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for name in [
+            "IMG_20230514_081522_1.jpg",
+            "IMG_20230514_081522_2.jpg",
+            "IMG_20230514_090000.jpg",
+        ] {
+            fs::write(root.join(name), name).unwrap();
+        }
+        let options = SortOptions {
+            bursts: true,
+            burst_folders: true,
+            ..options(root)
+        };
+        crate::apply(&plan(scan_jpg(root, false), &options).unwrap()).unwrap();
+        assert_eq!(
+            list(root),
+            vec![
+                "2__IMG_20230514_090000.jpg",
+                "burst-1",
+                "burst-1/1a__IMG_20230514_081522_1.jpg",
+                "burst-1/1b__IMG_20230514_081522_2.jpg",
+            ]
+        );
+
+        // Bursts are numbered again with new photos, though their folders are not scanned.
+        fs::write(root.join("IMG_20230514_070000.jpg"), "new").unwrap();
+        crate::apply(&plan(scan_jpg(root, false), &options).unwrap()).unwrap();
+        assert_eq!(
+            list(root),
+            vec![
+                "1__IMG_20230514_070000.jpg",
+                "3__IMG_20230514_090000.jpg",
+                "burst-2",
+                "burst-2/2a__IMG_20230514_081522_1.jpg",
+                "burst-2/2b__IMG_20230514_081522_2.jpg",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_photos_without_offset_and_videos() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("IMG_20230514_100000.jpg"), "photo").unwrap();
+        fs::write(root.join("IMG_20230514_100200.jpg"), "photo").unwrap();
+        // Android video with `mvhd` in UTC only, taken at 10:01 by the local clock.
+        let local = NaiveDate::from_ymd_opt(2023, 5, 14)
+            .and_then(|date| date.and_hms_opt(10, 1, 0))
+            .unwrap();
+        let utc = Local.from_local_datetime(&local).earliest().unwrap();
+        let seconds = (utc.timestamp() + 2_082_844_800) as u32;
+        let mvhd = make_box(
+            b"mvhd",
+            &[vec![0; 4], seconds.to_be_bytes().to_vec()].concat(),
+        );
+        let mp4 = [make_box(b"ftyp", b"isom"), make_box(b"moov", &mvhd)].concat();
+        fs::write(root.join("VID.mp4"), mp4).unwrap();
+
+        let mut options = options(root);
+        options.reader = TimeReader::new(
+            &[TimeSource::Container, TimeSource::Filename],
+            &FilenamePattern::builtin(),
+        );
+        let files = scan(
+            root,
+            &ScanOptions {
+                extensions: vec![String::from("jpg"), String::from("mp4")],
+                magic: false,
+                max_depth: 1,
+                follow_symlinks: false,
+                hidden: false,
+            },
+        )
+        .unwrap();
+        let plan = plan(files, &options).unwrap();
+        let targets: Vec<_> = plan
+            .ops
+            .iter()
+            .map(|op| op.to.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                "1__IMG_20230514_100000.jpg",
+                "2__VID.mp4",
+                "3__IMG_20230514_100200.jpg"
+            ]
+        );
+    }
+}
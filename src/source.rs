@@ -0,0 +1,276 @@
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use exif::{Exif, Tag};
+use std::{cell::OnceCell, fs, path::Path};
+
+use crate::{
+    filename::{filename_time, FilenamePattern},
+    formats::{exif_ascii, read_exif},
+    sidecar::sidecar_time,
+    timestamp::{CaptureTime, Timestamp},
+    video::read_container_time,
+};
+
+/// Reads the capture time of a file from one kind of metadata.
+///
+/// Implement this to read formats that are not built in, and give it to `TimeReader`.
+pub trait MetadataSource: Send + Sync {
+    /// Name shown with the time, e.g. `exif-original`.
+    fn name(&self) -> &'static str;
+
+    /// Reads the capture time of the file.
+    fn read(&self, file: &SourceFile) -> Option<CaptureTime>;
+
+    /// Whether the time is from the camera clock, to which the clock offset of the camera applies.
+    fn camera_clock(&self) -> bool {
+        true
+    }
+}
+
+/// File whose capture time is read by sources. Its Exif is read once, when first needed.
+pub struct SourceFile<'a> {
+    path: &'a Path,
+    exif: OnceCell<Vec<Exif>>,
+}
+
+impl<'a> SourceFile<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            exif: OnceCell::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path
+    }
+
+    /// Exif of the file, empty for videos and files without Exif.
+    pub(crate) fn exif(&self) -> &[Exif] {
+        self.exif.get_or_init(|| read_exif(self.path))
+    }
+}
+
+/// Built-in metadata sources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TimeSource {
+    /// Exif `DateTimeOriginal`
+    ExifOriginal,
+    /// Exif `DateTimeDigitized`
+    ExifDigitized,
+    /// Exif `DateTime`
+    ExifDatetime,
+    /// Creation time in MP4/QuickTime container (`©day` or `mvhd`)
+    Container,
+    /// Date taken in XMP or Google Takeout JSON sidecar
+    Sidecar,
+    /// Date and time in the file name
+    Filename,
+    /// Last modified time of the file
+    Mtime,
+}
+
+impl TimeSource {
+    /// Makes the source. `patterns` are tried in order by `filename`.
+    pub fn metadata_source(self, patterns: &[FilenamePattern]) -> Box<dyn MetadataSource> {
+        match self {
+            Self::ExifOriginal => Box::new(ExifTime::ORIGINAL),
+            Self::ExifDigitized => Box::new(ExifTime::DIGITIZED),
+            Self::ExifDatetime => Box::new(ExifTime::DATETIME),
+            Self::Container => Box::new(ContainerTime),
+            Self::Sidecar => Box::new(SidecarTime),
+            Self::Filename => Box::new(FilenameTime(patterns.to_vec())),
+            Self::Mtime => Box::new(Mtime),
+        }
+    }
+}
+
+/// Exif `DateTime*` tag with its `SubSecTime*` and `OffsetTime*`.
+pub struct ExifTime {
+    name: &'static str,
+    datetime: Tag,
+    subsec: Tag,
+    offset: Tag,
+}
+
+impl ExifTime {
+    pub const ORIGINAL: Self = Self {
+        name: "exif-original",
+        datetime: Tag::DateTimeOriginal,
+        subsec: Tag::SubSecTimeOriginal,
+        offset: Tag::OffsetTimeOriginal,
+    };
+    pub const DIGITIZED: Self = Self {
+        name: "exif-digitized",
+        datetime: Tag::DateTimeDigitized,
+        subsec: Tag::SubSecTimeDigitized,
+        offset: Tag::OffsetTimeDigitized,
+    };
+    pub const DATETIME: Self = Self {
+        name: "exif-datetime",
+        datetime: Tag::DateTime,
+        subsec: Tag::SubSecTime,
+        offset: Tag::OffsetTime,
+    };
+}
+
+impl MetadataSource for ExifTime {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn read(&self, file: &SourceFile) -> Option<CaptureTime> {
+        let exif = file.exif();
+        CaptureTime::from_exif(
+            &exif_ascii(exif, self.datetime)?,
+            exif_ascii(exif, self.subsec).as_deref(),
+            exif_ascii(exif, self.offset).as_deref(),
+        )
+    }
+}
+
+/// Creation time in MP4/QuickTime container.
+pub struct ContainerTime;
+
+impl MetadataSource for ContainerTime {
+    fn name(&self) -> &'static str {
+        "container"
+    }
+
+    fn read(&self, file: &SourceFile) -> Option<CaptureTime> {
+        read_container_time(file.path())
+    }
+}
+
+/// Date taken in the XMP or Google Takeout JSON sidecar next to the file.
+pub struct SidecarTime;
+
+impl MetadataSource for SidecarTime {
+    fn name(&self) -> &'static str {
+        "sidecar"
+    }
+
+    fn read(&self, file: &SourceFile) -> Option<CaptureTime> {
+        sidecar_time(file.path())
+    }
+}
+
+/// Date and time in the file name, found by the patterns tried in order.
+pub struct FilenameTime(pub Vec<FilenamePattern>);
+
+impl MetadataSource for FilenameTime {
+    fn name(&self) -> &'static str {
+        "filename"
+    }
+
+    fn read(&self, file: &SourceFile) -> Option<CaptureTime> {
+        filename_time(&file.path().file_name()?.to_string_lossy(), &self.0)
+    }
+}
+
+/// Last modified time of the file, by the clock of the computer.
+pub struct Mtime;
+
+impl MetadataSource for Mtime {
+    fn name(&self) -> &'static str {
+        "mtime"
+    }
+
+    fn read(&self, file: &SourceFile) -> Option<CaptureTime> {
+        let modified = fs::metadata(file.path()).ok()?.modified().ok()?;
+        let modified: DateTime<Local> = modified.into();
+        Some(CaptureTime {
+            local: modified.naive_local(),
+            offset: Some(*modified.offset()),
+        })
+    }
+
+    fn camera_clock(&self) -> bool {
+        false
+    }
+}
+
+/// Reads capture times by the sources, tried in order.
+pub struct TimeReader {
+    pub sources: Vec<Box<dyn MetadataSource>>,
+}
+
+impl TimeReader {
+    /// Reader of the built-in sources. `patterns` are tried in order by `filename`.
+    pub fn new(sources: &[TimeSource], patterns: &[FilenamePattern]) -> Self {
+        Self {
+            sources: sources
+                .iter()
+                .map(|source| source.metadata_source(patterns))
+                .collect(),
+        }
+    }
+
+    /// Reads the capture time from the first source that has it.
+    pub fn read(&self, file: &SourceFile) -> Option<Timestamp> {
+        self.sources.iter().find_map(|source| {
+            source.read(file).map(|time| Timestamp {
+                time,
+                source: source.name(),
+                camera_clock: source.camera_clock(),
+                correction: None,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Source of a fixed time for files of one name.
+    struct Catalog(&'static str);
+
+    impl MetadataSource for Catalog {
+        fn name(&self) -> &'static str {
+            "catalog"
+        }
+
+        fn read(&self, file: &SourceFile) -> Option<CaptureTime> {
+            (file.path().file_name()? == self.0)
+                .then(|| CaptureTime::from_exif("2024:05:01 10:00:00", None, None))
+                .flatten()
+        }
+
+        fn camera_clock(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_time_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        for name in ["a.jpg", "b.jpg", "c.jpg", "d.jpg"] {
+            fs::write(dir.join(name), "no exif").unwrap();
+        }
+        fs::write(
+            dir.join("a.xmp"),
+            r#"<rdf:Description exif:DateTimeOriginal="2024-04-01T08:00:00+02:00"/>"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.jpg.supplemental-metadata.json"),
+            r#"{"photoTakenTime": {"timestamp": "1714557600"}}"#,
+        )
+        .unwrap();
+
+        let mut reader = TimeReader::new(&[TimeSource::ExifOriginal, TimeSource::Sidecar], &[]);
+        reader.sources.insert(0, Box::new(Catalog("c.jpg")));
+        let read = |name| reader.read(&SourceFile::new(&dir.join(name)));
+
+        let a = read("a.jpg").unwrap();
+        assert_eq!((a.source, a.camera_clock), ("sidecar", true));
+        assert_eq!(a.time.to_string(), "2024-04-01 08:00:00.000+02:00");
+        let b = read("b.jpg").unwrap();
+        assert_eq!(b.time.to_string(), "2024-05-01 10:00:00.000+00:00");
+        let c = read("c.jpg").unwrap();
+        assert_eq!((c.source, c.camera_clock), ("catalog", false));
+        assert!(read("d.jpg").is_none());
+    }
+}
@@ -0,0 +1,442 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDateTime,
+};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    checksum::sha256,
+    error::{invalid, Error, Result},
+    metadata::Camera,
+    timestamp::Timestamp,
+};
+
+/// Format of `{date}` without a format.
+const DEFAULT_DATE_FORMAT: &str = "%Y%m%d-%H%M%S";
+/// Length of `{hash}` without a length.
+const DEFAULT_HASH_LEN: usize = 8;
+/// Value of placeholders whose metadata is not found.
+const UNKNOWN: &str = "unknown";
+/// Characters that can not be in file names on some systems.
+const INVALID_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Template of new file names, e.g. `{seq:04}_{date:%Y%m%d-%H%M%S}_{model}_{stem}.{ext}`.
+#[derive(Clone, Debug)]
+pub struct Template(Vec<Part>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// Sequence number, zero padded to the width.
+    Seq(Option<usize>),
+    /// Event number, zero padded to the width.
+    Event(Option<usize>),
+    /// Capture time in strftime format.
+    Date(String),
+    Make,
+    Model,
+    Lens,
+    /// Original name without the extension.
+    Stem,
+    /// Original extension without the dot.
+    Ext,
+    /// Original name.
+    Name,
+    /// Leading hex digits of SHA-256 of the file.
+    Hash(usize),
+}
+
+/// Template of folders under the output directory, e.g. `{date:%Y}/{date:%m}/{date:%d}`.
+#[derive(Clone, Debug)]
+pub struct Layout(Template);
+
+/// Position of a frame in a burst, shown as letters after `{seq}`.
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub index: usize,
+    /// Number of frames in the burst.
+    pub count: usize,
+}
+
+/// Values of placeholders for one file.
+pub struct Fields<'a> {
+    pub seq: usize,
+    /// Width of `{seq}` without a width.
+    pub seq_width: usize,
+    pub frame: Option<Frame>,
+    pub event: usize,
+    /// Width of `{event}` without a width.
+    pub event_width: usize,
+    pub timestamp: Option<&'a Timestamp>,
+    pub camera: &'a Camera,
+    /// Original name of the file.
+    pub name: &'a str,
+    /// File to be hashed for `{hash}`.
+    pub path: &'a Path,
+}
+
+impl Template {
+    /// `{seq}<delim>{name}`, the default naming, or `{event}-{seq}<delim>{name}` with events.
+    pub fn prefix(delim: &str, events: bool) -> Self {
+        let mut parts = vec![
+            Part::Seq(None),
+            Part::Literal(String::from(delim)),
+            Part::Name,
+        ];
+        if events {
+            parts.splice(0..0, [Part::Event(None), Part::Literal(String::from("-"))]);
+        }
+        Self(parts)
+    }
+
+    pub fn uses_event(&self) -> bool {
+        self.0.iter().any(|part| matches!(part, Part::Event(_)))
+    }
+
+    /// Whether the template shows the camera, which is read from Exif.
+    pub fn uses_camera(&self) -> bool {
+        self.0
+            .iter()
+            .any(|part| matches!(part, Part::Make | Part::Model | Part::Lens))
+    }
+
+    /// Parses the template. `/` separates folders if `dirs` is true.
+    fn parse(s: &str, dirs: bool) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| {
+                        Error::Invalid(format!("Placeholder in {s} is not closed."))
+                    })?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_placeholder(&rest[..end], dirs)?);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => invalid!("Unmatched }} in {s}, write }}}} for a literal."),
+                '/' if dirs => literal.push('/'),
+                c if INVALID_CHARS.contains(&c) => {
+                    invalid!("Template {s} has {c} outside placeholders.")
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self(parts))
+    }
+
+    pub fn render(&self, fields: &Fields) -> Result<String> {
+        let path = Path::new(fields.name);
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+        let ext = path.extension().map(|ext| ext.to_string_lossy());
+
+        let mut name = String::new();
+        for part in self.0.iter() {
+            match part {
+                Part::Literal(text) => name.push_str(text),
+                Part::Seq(width) => {
+                    name.push_str(&create_prefix(
+                        fields.seq,
+                        width.unwrap_or(fields.seq_width),
+                    ));
+                    if let Some(frame) = fields.frame {
+                        name.push_str(&frame.letters());
+                    }
+                }
+                Part::Event(width) => name.push_str(&create_prefix(
+                    fields.event,
+                    width.unwrap_or(fields.event_width),
+                )),
+                Part::Date(format) => match fields.timestamp {
+                    Some(timestamp) => {
+                        name.push_str(&timestamp.time.local.format(format).to_string())
+                    }
+                    None => name.push_str(UNKNOWN),
+                },
+                Part::Make => name.push_str(&clean(fields.camera.make.as_deref())),
+                Part::Model => name.push_str(&clean(fields.camera.model.as_deref())),
+                Part::Lens => name.push_str(&clean(fields.camera.lens.as_deref())),
+                Part::Stem => name.push_str(stem.as_deref().unwrap_or_default()),
+                Part::Ext => name.push_str(ext.as_deref().unwrap_or_default()),
+                Part::Name => name.push_str(fields.name),
+                Part::Hash(len) => {
+                    let hash = sha256(fields.path).map_err(Error::io("read", fields.path))?;
+                    name.push_str(&hash[..*len])
+                }
+            }
+        }
+
+        if name.is_empty() || name == "." || name == ".." {
+            invalid!(
+                "Template makes an invalid name {name:?} for {}.",
+                fields.name
+            );
+        }
+        Ok(name)
+    }
+}
+
+impl Layout {
+    pub fn uses_event(&self) -> bool {
+        self.0.uses_event()
+    }
+
+    pub fn uses_camera(&self) -> bool {
+        self.0.uses_camera()
+    }
+
+    /// Renders the relative path of the folder.
+    pub fn render(&self, fields: &Fields) -> Result<PathBuf> {
+        let path = self.0.render(fields)?;
+        let components: Vec<&str> = path.split('/').collect();
+        if components
+            .iter()
+            .any(|c| c.is_empty() || *c == "." || *c == "..")
+        {
+            invalid!("Layout makes an invalid path {path:?} for {}.", fields.name);
+        }
+        Ok(components.iter().collect())
+    }
+}
+
+impl Frame {
+    /// `a` to `z`, or `aa`, `ab`, ... for bursts of more than 26 frames, so that names keep the order.
+    pub fn letters(&self) -> String {
+        let mut width = 1;
+        while 26usize.pow(width) < self.count {
+            width += 1;
+        }
+        (0..width)
+            .rev()
+            .map(|digit| (b'a' + (self.index / 26usize.pow(digit) % 26) as u8) as char)
+            .collect()
+    }
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, false)
+    }
+}
+
+impl FromStr for Layout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = match s {
+            "YYYY/MM/DD" => "{date:%Y}/{date:%m}/{date:%d}",
+            "YYYY/YYYY-MM-DD" => "{date:%Y}/{date:%Y-%m-%d}",
+            _ => s,
+        };
+        let template = Template::parse(s, true)?;
+        if template.0.iter().any(|part| matches!(part, Part::Seq(_))) {
+            invalid!("Layout {s} can not have {{seq}}, which is numbered in each folder.");
+        }
+        Ok(Self(template))
+    }
+}
+
+fn parse_placeholder(placeholder: &str, dirs: bool) -> Result<Part> {
+    let (name, spec) = match placeholder.split_once(':') {
+        Some((name, spec)) => (name, Some(spec)),
+        None => (placeholder, None),
+    };
+    let number = |spec: &str| {
+        spec.parse::<usize>()
+            .map_err(|_| Error::Invalid(format!("{spec} in {{{placeholder}}} is not a number.")))
+    };
+    let width = |spec: &str| {
+        let width = number(spec)?;
+        if !(1..=20).contains(&width) {
+            invalid!("Width of {{{placeholder}}} needs to be 1 to 20.");
+        }
+        Ok(width)
+    };
+
+    Ok(match (name, spec) {
+        ("seq", None) => Part::Seq(None),
+        ("seq", Some(spec)) => Part::Seq(Some(width(spec)?)),
+        ("event", None) => Part::Event(None),
+        ("event", Some(spec)) => Part::Event(Some(width(spec)?)),
+        ("date", format) => {
+            let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+            // Formats that need a time zone fail on naive date and time.
+            let mut sample = String::new();
+            if StrftimeItems::new(format).any(|item| item == Item::Error)
+                || write!(sample, "{}", NaiveDateTime::default().format(format)).is_err()
+            {
+                invalid!("{format} in {{{placeholder}}} is not a valid date format.");
+            }
+            if sample.contains(|c| INVALID_CHARS.contains(&c) && !(dirs && c == '/')) {
+                invalid!("{format} in {{{placeholder}}} makes an invalid name.");
+            }
+            Part::Date(String::from(format))
+        }
+        ("hash", len) => {
+            let len = len.map(number).transpose()?.unwrap_or(DEFAULT_HASH_LEN);
+            if !(1..=64).contains(&len) {
+                invalid!("Length of {{{placeholder}}} needs to be 1 to 64.");
+            }
+            Part::Hash(len)
+        }
+        ("make", None) => Part::Make,
+        ("model", None) => Part::Model,
+        ("lens", None) => Part::Lens,
+        ("stem", None) => Part::Stem,
+        ("ext", None) => Part::Ext,
+        ("name", None) => Part::Name,
+        _ => invalid!("Unknown placeholder {{{placeholder}}}."),
+    })
+}
+
+fn create_prefix(num: usize, len: usize) -> String {
+    let num = num.to_string();
+
+    if len < num.len() {
+        num
+    } else {
+        let mut prefix = String::new();
+        for _i in 0..(len - num.len()) {
+            prefix.push('0');
+        }
+        prefix.push_str(&num);
+        prefix
+    }
+}
+
+/// Replaces characters that can not be in file names.
+fn clean(value: Option<&str>) -> String {
+    match value.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => value
+            .chars()
+            .map(|c| {
+                if INVALID_CHARS.contains(&c) || c.is_control() {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect(),
+        None => String::from(UNKNOWN),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::timestamp::CaptureTime;
+
+    #[test]
+    fn test_create_prefix() {
+        let actual = create_prefix(10, 3);
+        assert_eq!(actual, String::from("010"));
+
+        let actual = create_prefix(5, 3);
+        assert_eq!(actual, String::from("005"));
+
+        let actual = create_prefix(100, 2);
+        assert_eq!(actual, String::from("100"));
+    }
+
+    #[test]
+    fn test_render() {
+        let template: Template = "{seq:04}_{date}_{model}_{lens}_{stem}.{ext}"
+            .parse()
+            .unwrap();
+        let timestamp = Timestamp {
+            time: CaptureTime::from_exif("2024:05:01 12:03:04", None, None).unwrap(),
+            source: "exif-original",
+            camera_clock: true,
+            correction: None,
+        };
+        let camera = Camera {
+            model: Some(String::from("Canon EOS R6")),
+            ..Default::default()
+        };
+        let fields = Fields {
+            seq: 7,
+            seq_width: 2,
+            frame: None,
+            event: 3,
+            event_width: 1,
+            timestamp: Some(&timestamp),
+            camera: &camera,
+            name: "IMG_1234.JPG",
+            path: Path::new("IMG_1234.JPG"),
+        };
+        assert_eq!(
+            template.render(&fields).unwrap(),
+            "0007_20240501-120304_Canon EOS R6_unknown_IMG_1234.JPG"
+        );
+
+        let template = Template::prefix("__", false);
+        assert_eq!(template.render(&fields).unwrap(), "07__IMG_1234.JPG");
+        let template = Template::prefix("__", true);
+        assert_eq!(template.render(&fields).unwrap(), "3-07__IMG_1234.JPG");
+        let frame = Some(Frame { index: 1, count: 3 });
+        let template = Template::prefix("__", false);
+        assert_eq!(
+            template.render(&Fields { frame, ..fields }).unwrap(),
+            "07b__IMG_1234.JPG"
+        );
+        let frame = Frame {
+            index: 27,
+            count: 40,
+        };
+        assert_eq!(frame.letters(), "bb");
+
+        for invalid in [
+            "{seq}{date:%D}",
+            "{seq}{date:%z}",
+            "{seq}{",
+            "{seq}}",
+            "{seq}/{stem}",
+            "{seq}{foo}",
+            "{seq:x}",
+            "{seq:0}",
+            "{seq:99999999999999}",
+            "{event:21}{seq}",
+        ] {
+            assert!(invalid.parse::<Template>().is_err(), "{invalid}");
+        }
+
+        let layout: Layout = "YYYY/YYYY-MM-DD".parse().unwrap();
+        assert_eq!(
+            layout.render(&fields).unwrap(),
+            PathBuf::from("2024/2024-05-01")
+        );
+        let layout: Layout = "{model}/{date:%Y/%m}".parse().unwrap();
+        assert_eq!(
+            layout.render(&fields).unwrap(),
+            PathBuf::from("Canon EOS R6/2024/05")
+        );
+        assert!("{date:%Y}/{seq}".parse::<Layout>().is_err());
+        assert!("{date:%Y}//{stem}"
+            .parse::<Layout>()
+            .unwrap()
+            .render(&fields)
+            .is_err());
+    }
+}
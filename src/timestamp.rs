@@ -0,0 +1,145 @@
+use chrono::{FixedOffset, Local, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use std::{cmp::Ordering, fmt};
+
+use crate::config::ClockOffset;
+
+const EXIF_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// Capture time of a photo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureTime {
+    /// Wall clock time of the camera, including sub-seconds.
+    pub local: NaiveDateTime,
+    /// Offset from UTC if the camera recorded it.
+    pub offset: Option<FixedOffset>,
+}
+
+/// Offset of the local time zone at the wall clock time.
+fn local_offset(local: &NaiveDateTime) -> FixedOffset {
+    match Local.offset_from_local_datetime(local).earliest() {
+        Some(offset) => offset,
+        // Skipped by a daylight saving change.
+        None => Local.offset_from_utc_datetime(local).fix(),
+    }
+}
+
+impl CaptureTime {
+    /// Time in UTC used for sorting. Times without an offset are in the local time zone,
+    /// like the clock of the camera that was set by hand.
+    pub fn instant(&self) -> NaiveDateTime {
+        let offset = self.offset.unwrap_or_else(|| local_offset(&self.local));
+        self.local - TimeDelta::seconds(offset.local_minus_utc() as i64)
+    }
+
+    /// Parses Exif `DateTime*`, `SubSecTime*` and `OffsetTime*` values.
+    pub fn from_exif(datetime: &str, subsec: Option<&str>, offset: Option<&str>) -> Option<Self> {
+        let mut local = NaiveDateTime::parse_from_str(datetime.trim(), EXIF_FORMAT).ok()?;
+        if let Some(nanos) = subsec.and_then(parse_subsec) {
+            local += TimeDelta::nanoseconds(nanos);
+        }
+        let offset = offset.and_then(|offset| offset.trim().parse::<FixedOffset>().ok());
+        Some(Self { local, offset })
+    }
+}
+
+impl Ord for CaptureTime {
+    /// Orders by the instant, then by the wall clock time. Times without an offset come
+    /// before times with the local offset, so that the order agrees with `Eq`.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let offset = |time: &Self| time.offset.map(|offset| offset.local_minus_utc());
+        self.instant()
+            .cmp(&other.instant())
+            .then_with(|| self.local.cmp(&other.local))
+            .then_with(|| offset(self).cmp(&offset(other)))
+    }
+}
+
+impl PartialOrd for CaptureTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Capture time with the source it was taken from.
+#[derive(Clone, Copy, Debug)]
+pub struct Timestamp {
+    pub time: CaptureTime,
+    /// Name of the `MetadataSource`, e.g. `exif-original`.
+    pub source: &'static str,
+    /// Whether the time is from the camera clock, to which the clock offset of the camera applies.
+    pub camera_clock: bool,
+    /// Clock offset of the camera already added to `time`.
+    pub correction: Option<ClockOffset>,
+}
+
+impl fmt::Display for CaptureTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.local.format("%Y-%m-%d %H:%M:%S%.3f"))?;
+        if let Some(offset) = self.offset {
+            write!(f, "{offset}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Timestamp {
+    /// Adds the clock offset of the camera. Times of the computer (e.g. `mtime`) are left as is.
+    pub fn correct(&mut self, offset: ClockOffset) {
+        if !self.camera_clock {
+            return;
+        }
+        // Offsets that push the time out of range are left out.
+        if let Some(local) = self.time.local.checked_add_signed(offset.0) {
+            self.time.local = local;
+            self.correction = Some(offset);
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.correction {
+            Some(offset) => write!(f, "{} ({}, corrected {offset})", self.time, self.source),
+            None => write!(f, "{} ({})", self.time, self.source),
+        }
+    }
+}
+
+/// Converts sub-second digits (e.g. `"05"` is 50 ms) into nanoseconds.
+fn parse_subsec(subsec: &str) -> Option<i64> {
+    let digits = subsec.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = digits.chars().chain("000000000".chars()).take(9).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_exif() {
+        let tokyo =
+            CaptureTime::from_exif("2024:05:01 10:00:00", Some("5"), Some("+09:00")).unwrap();
+        let london = CaptureTime::from_exif("2024:05:01 02:00:01", None, Some("+01:00")).unwrap();
+        assert_eq!(tokyo.local.and_utc().timestamp_subsec_millis(), 500);
+        assert!(tokyo < london);
+
+        let burst1 = CaptureTime::from_exif("2024:05:01 10:00:00", Some("120"), None).unwrap();
+        let burst2 = CaptureTime::from_exif("2024:05:01 10:00:00", Some("34"), None).unwrap();
+        assert!(burst1 < burst2);
+
+        let local = CaptureTime::from_exif("2024:05:01 10:00:00", None, None).unwrap();
+        let offset = CaptureTime {
+            offset: Some(local_offset(&local.local)),
+            ..local
+        };
+        assert_eq!(local.instant(), offset.instant());
+        assert_ne!(local, offset);
+        assert_eq!(local.cmp(&offset), Ordering::Less);
+
+        assert!(CaptureTime::from_exif("0000:00:00 00:00:00", None, None).is_none());
+    }
+}
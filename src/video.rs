@@ -0,0 +1,244 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeDelta};
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::timestamp::CaptureTime;
+
+/// `moov` larger than this is not read.
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+/// Key of the creation date in `meta` of iPhone videos.
+const APPLE_CREATION_DATE: &[u8] = b"com.apple.quicktime.creationdate";
+
+/// Reads the creation time from MP4/QuickTime container.
+///
+/// `©day` is preferred because it keeps the local time and offset.
+/// Falls back to `mvhd` creation time, which is in UTC.
+pub fn read_container_time(path: &Path) -> Option<CaptureTime> {
+    let moov = read_moov(&mut fs::File::open(path).ok()?)?;
+    day_time(&moov).or_else(|| mvhd_time(&moov))
+}
+
+/// Finds top level `moov` box and returns its content.
+pub fn read_moov<R: Read + Seek>(reader: &mut R) -> Option<Vec<u8>> {
+    let file_len = reader.seek(SeekFrom::End(0)).ok()?;
+    let mut pos: u64 = 0;
+    while pos.checked_add(8).is_some_and(|end| end <= file_len) {
+        reader.seek(SeekFrom::Start(pos)).ok()?;
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).ok()?;
+        let mut header_len = 8;
+        let size = match u32::from_be_bytes(header[0..4].try_into().unwrap()) {
+            0 => file_len - pos,
+            1 => {
+                let mut large = [0; 8];
+                reader.read_exact(&mut large).ok()?;
+                header_len = 16;
+                u64::from_be_bytes(large)
+            }
+            size => size as u64,
+        };
+        if size < header_len {
+            return None;
+        }
+        if &header[4..8] == b"moov" {
+            let len = size - header_len;
+            if len > MAX_MOOV_SIZE {
+                return None;
+            }
+            let mut moov = vec![0; len as usize];
+            reader.read_exact(&mut moov).ok()?;
+            return Some(moov);
+        }
+        pos = pos.checked_add(size)?;
+    }
+    None
+}
+
+/// Iterates child boxes as pairs of type and content.
+pub fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(pos..pos + 8)?;
+        let size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let (header_len, size) = match size {
+            0 => (8, data.len() - pos),
+            1 => {
+                let large = data.get(pos + 8..pos + 16)?;
+                (16, u64::from_be_bytes(large.try_into().unwrap()) as usize)
+            }
+            size => (8, size),
+        };
+        if size < header_len {
+            return None;
+        }
+        let content = data.get(pos + header_len..pos.checked_add(size)?)?;
+        let kind = &header[4..8];
+        pos = pos.checked_add(size)?;
+        Some((kind, content))
+    })
+}
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data)
+        .find(|(k, _)| *k == kind)
+        .map(|(_, content)| content)
+}
+
+fn mvhd_time(moov: &[u8]) -> Option<CaptureTime> {
+    let mvhd = find_box(moov, b"mvhd")?;
+    let seconds = match mvhd.first()? {
+        0 => u32::from_be_bytes(mvhd.get(4..8)?.try_into().unwrap()) as i64,
+        1 => i64::try_from(u64::from_be_bytes(mvhd.get(4..12)?.try_into().unwrap())).ok()?,
+        _ => return None,
+    };
+    // Zero means the time was not set.
+    if seconds == 0 {
+        return None;
+    }
+    let epoch = NaiveDate::from_ymd_opt(1904, 1, 1)?.and_hms_opt(0, 0, 0)?;
+    Some(CaptureTime {
+        local: epoch.checked_add_signed(TimeDelta::try_seconds(seconds)?)?,
+        offset: FixedOffset::east_opt(0),
+    })
+}
+
+/// Reads `©day` from `udta` or `meta`, or Apple creation date from `meta`.
+fn day_time(moov: &[u8]) -> Option<CaptureTime> {
+    let from_meta = meta_item(moov, APPLE_CREATION_DATE)
+        .or_else(|| {
+            find_box(find_meta(moov)?, b"ilst").and_then(|ilst| item_data(ilst, b"\xa9day"))
+        })
+        .and_then(parse_date);
+    from_meta.or_else(|| {
+        // QuickTime style `©day` has 16-bit length and language before the text.
+        let day = find_box(find_box(moov, b"udta")?, b"\xa9day")?;
+        let len = u16::from_be_bytes(day.get(0..2)?.try_into().unwrap()) as usize;
+        parse_date(day.get(4..4 + len)?)
+    })
+}
+
+/// Finds `meta` in `moov` or `moov/udta` and returns its children.
+fn find_meta(moov: &[u8]) -> Option<&[u8]> {
+    let meta = find_box(moov, b"meta")
+        .or_else(|| find_box(moov, b"udta").and_then(|udta| find_box(udta, b"meta")))?;
+    Some(meta_children(meta))
+}
+
+/// Reads the value of `meta` item named `key` in `keys`, such as Apple QuickTime metadata.
+pub fn meta_item<'a>(moov: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let meta = find_meta(moov)?;
+    let index = key_index(find_box(meta, b"keys")?, key)?;
+    item_data(find_box(meta, b"ilst")?, &index.to_be_bytes())
+}
+
+/// Reads `data` of the item in `ilst`, without its type and locale.
+fn item_data<'a>(ilst: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    find_box(find_box(ilst, kind)?, b"data")?.get(8..)
+}
+
+/// Skips version and flags of `meta` if it is an MP4 style full box.
+fn meta_children(meta: &[u8]) -> &[u8] {
+    match meta.get(4..8) {
+        Some(b"hdlr") => meta,
+        _ => meta.get(4..).unwrap_or_default(),
+    }
+}
+
+/// Finds 1-based index of `key` in `keys` box.
+fn key_index(keys: &[u8], key: &[u8]) -> Option<u32> {
+    let count = u32::from_be_bytes(keys.get(4..8)?.try_into().unwrap());
+    let mut pos = 8;
+    for index in 1..=count {
+        let size = u32::from_be_bytes(keys.get(pos..pos + 4)?.try_into().unwrap()) as usize;
+        if keys.get(pos + 8..pos + size)? == key {
+            return Some(index);
+        }
+        pos += size.max(8);
+    }
+    None
+}
+
+fn parse_date(text: &[u8]) -> Option<CaptureTime> {
+    let text = String::from_utf8_lossy(text);
+    let text = text.trim_end_matches('\0').trim();
+
+    for format in ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%dT%H:%M:%S%.f%:z"] {
+        if let Ok(time) = DateTime::parse_from_str(text, format) {
+            return Some(CaptureTime {
+                local: time.naive_local(),
+                offset: Some(*time.offset()),
+            });
+        }
+    }
+    if let Some(utc) = text.strip_suffix('Z') {
+        let local = NaiveDateTime::parse_from_str(utc, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        return Some(CaptureTime {
+            local,
+            offset: FixedOffset::east_opt(0),
+        });
+    }
+    let local = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    Some(CaptureTime {
+        local,
+        offset: None,
+    })
+}
+
+/// Makes a box of the kind around the content.
+#[cfg(test)]
+pub(crate) fn make_box(kind: &[u8], content: &[u8]) -> Vec<u8> {
+    let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(content);
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_container_time() {
+        // 2023-05-14 01:15:22 UTC
+        let seconds: u32 = 3_766_871_722;
+        let mut mvhd = vec![0; 4];
+        mvhd.extend_from_slice(&seconds.to_be_bytes());
+        let mvhd = make_box(b"mvhd", &mvhd);
+
+        let ftyp = make_box(b"ftyp", b"qt  ");
+        let moov = make_box(b"moov", &mvhd);
+        let file = [ftyp, moov].concat();
+        let moov = read_moov(&mut Cursor::new(file)).unwrap();
+        let time = mvhd_time(&moov).unwrap();
+        assert_eq!(time.local.to_string(), "2023-05-14 01:15:22");
+
+        let mut day = vec![0, 24, 0x15, 0xc7];
+        day.extend_from_slice(b"2023-05-14T10:15:22+0900");
+        let udta = make_box(b"udta", &make_box(b"\xa9day", &day));
+        let moov = [mvhd, udta].concat();
+        let time = day_time(&moov).unwrap();
+        assert_eq!(time.instant().to_string(), "2023-05-14 01:15:22");
+        assert_eq!(time.local.to_string(), "2023-05-14 10:15:22");
+    }
+
+    #[test]
+    fn test_malformed_boxes() {
+        // Version 1 `mvhd` whose creation time is out of range.
+        let mut mvhd = vec![1, 0, 0, 0];
+        mvhd.extend_from_slice(&(i64::MAX as u64 / 2).to_be_bytes());
+        assert!(mvhd_time(&make_box(b"mvhd", &mvhd)).is_none());
+        mvhd[4..12].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(mvhd_time(&make_box(b"mvhd", &mvhd)).is_none());
+
+        // 64-bit box size that overflows the position.
+        let mut huge = vec![0, 0, 0, 1];
+        huge.extend_from_slice(b"free");
+        huge.extend_from_slice(&(u64::MAX - 4).to_be_bytes());
+        let file = [make_box(b"ftyp", b"qt  "), huge].concat();
+        assert!(read_moov(&mut Cursor::new(file)).is_none());
+    }
+}